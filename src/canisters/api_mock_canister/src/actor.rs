@@ -1,11 +1,14 @@
 use crate::errors::{BooleanActorResponse, MockError, ServiceResult};
 use crate::mock_utils::FirstLevelName;
+use crate::quota::{BatchTransferQuotaResponse, BatchTransferRequest, QuotaType};
 use crate::state::{
     canister_module_init, is_approved_to, is_name_owner, must_not_anonymous, set_approval,
     validate_name, State, STATE,
 };
 use candid::{candid_method, CandidType, Deserialize, Principal};
+use common::errors::{merge_error_catalogs, CommonError, ErrorCode, ErrorCodeEntry};
 use common::permissions::{is_admin, must_be_system_owner};
+use common::state::stable_memory::StableStorageConfig;
 use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::{debug, info};
@@ -17,16 +20,17 @@ pub struct GetNamesResponse {
     pub names: Vec<(String, String)>,
 }
 
+common::stable_state_upgrade_hooks!(STATE, StableStorageConfig::default(), canister_module_init);
+
 #[init]
 #[candid_method(init)]
 fn init_function() {
     let owner = api::id();
     STATE.with(|state| {
         let mut map = state.borrow().registries.borrow_mut();
-        map.insert(String::from("user1.org"), owner.clone());
-        map.insert(String::from("user2.org"), owner.clone());
-        map.insert(String::from("user3.org"), owner.clone());
-        map.insert(String::from("user4.org"), owner.clone());
+        for name in ["user1.org", "user2.org", "user3.org", "user4.org"] {
+            map.insert(String::from(name), owner.clone()).unwrap();
+        }
     });
     canister_module_init();
 }
@@ -49,7 +53,7 @@ fn get_names() -> ServiceResult<GetNamesResponse> {
 
 #[update(name = "approve")]
 #[candid_method(update, rename = "approve")]
-async fn approve(name: String, to: Principal) -> BooleanActorResponse {
+async fn approve(name: String, to: Principal, locale: Option<String>) -> BooleanActorResponse {
     // if MOCK_RESULT_SUCCESS {
     //     Ok(BooleanActorResponse::Ok(true))
     // } else {
@@ -60,13 +64,17 @@ async fn approve(name: String, to: Principal) -> BooleanActorResponse {
     // }
     let caller = api::caller();
     debug!("approve: name={}, to={}", name, to);
-    set_approval(&FirstLevelName::from(name), &to);
-    BooleanActorResponse::Ok(true)
+    let result = set_approval(&FirstLevelName::from(name), &to).map(|_| true);
+    BooleanActorResponse::new(result).localize(locale.as_deref())
 }
 
 #[update(name = "transfer_from")]
 #[candid_method(update, rename = "transfer_from")]
-async fn transfer_from(name: String) -> BooleanActorResponse {
+async fn transfer_from(name: String, locale: Option<String>) -> BooleanActorResponse {
+    transfer_from_caller(name).localize(locale.as_deref())
+}
+
+fn transfer_from_caller(name: String) -> BooleanActorResponse {
     let caller = &api::caller();
     let owner = &api::id();
     debug!(
@@ -83,18 +91,25 @@ async fn transfer_from(name: String) -> BooleanActorResponse {
     STATE.with(|state| {
         let mut registries = state.borrow().registries.borrow_mut();
         //get registries by name and replace value with caller, if not exist then return error
-        if let Some(registry) = registries.get_mut(&name) {
-            *registry = caller.clone();
-        } else {
+        if !registries.contains_key(&name) {
             return BooleanActorResponse::new(Err(MockError::RegistrationNotFound));
         }
-        BooleanActorResponse::Ok(true)
+        let result = registries.insert(name, caller.clone()).map(|_| true);
+        BooleanActorResponse::new(result.map_err(MockError::from))
     })
 }
 
 #[update(name = "transfer")]
 #[candid_method(update, rename = "transfer")]
-async fn transfer(name: String, new_owner: Principal) -> BooleanActorResponse {
+async fn transfer(
+    name: String,
+    new_owner: Principal,
+    locale: Option<String>,
+) -> BooleanActorResponse {
+    transfer_to(name, new_owner).localize(locale.as_deref())
+}
+
+fn transfer_to(name: String, new_owner: Principal) -> BooleanActorResponse {
     let caller = &api::caller();
 
     debug!(
@@ -109,18 +124,49 @@ async fn transfer(name: String, new_owner: Principal) -> BooleanActorResponse {
             STATE.with(|state| {
                 let mut registries = state.borrow().registries.borrow_mut();
                 //get registries by name and replace value with caller, if not exist then return error
-                if let Some(registry) = registries.get_mut(&name) {
-                    *registry = new_owner.clone();
-                } else {
+                if !registries.contains_key(&name) {
                     return BooleanActorResponse::new(Err(MockError::RegistrationNotFound));
                 }
-                BooleanActorResponse::Ok(true)
+                let result = registries.insert(name, new_owner.clone()).map(|_| true);
+                BooleanActorResponse::new(result.map_err(MockError::from))
             })
         }
         Err(e) => BooleanActorResponse::new(Err(e)),
     }
 }
 
+#[update(name = "add_quota")]
+#[candid_method(update, rename = "add_quota")]
+fn add_quota(to: Principal, quota_type: QuotaType, diff: u32) -> BooleanActorResponse {
+    if !is_admin(&api::caller()) {
+        return BooleanActorResponse::new(Err(MockError::OwnerOnly));
+    }
+    BooleanActorResponse::new(crate::quota::add_quota(to, quota_type, diff).map(|_| true))
+}
+
+#[query(name = "get_quota")]
+#[candid_method(query, rename = "get_quota")]
+fn get_quota(quota_type: QuotaType) -> ServiceResult<u32> {
+    Ok(crate::quota::get_quota(&api::caller(), quota_type))
+}
+
+/// Transfers all quotas of the request or none of them, with the result of every transfer.
+#[update(name = "batch_transfer_quota")]
+#[candid_method(update, rename = "batch_transfer_quota")]
+fn batch_transfer_quota(request: BatchTransferRequest) -> BatchTransferQuotaResponse {
+    let caller = api::caller();
+    if let Err(err) = must_not_anonymous(&caller) {
+        return BatchTransferQuotaResponse::Err(err.into());
+    }
+    BatchTransferQuotaResponse::new(crate::quota::transfer_quotas(&caller, request))
+}
+
+#[query(name = "get_error_codes")]
+#[candid_method(query, rename = "get_error_codes")]
+fn get_error_codes() -> Vec<ErrorCodeEntry> {
+    merge_error_catalogs(vec![CommonError::catalog(), MockError::catalog()])
+}
+
 candid::export_service!();
 
 #[query(name = "__get_candid_interface_tmp_hack")]
@@ -1,119 +1,124 @@
 use candid::{CandidType, Deserialize};
+use common::errors::i18n::register_messages;
+use common::errors::{CommonError, ErrorCode};
+use common::http::HttpResponse;
+use common::state::collections::StableCollectionError;
+use log::error;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+#[cfg(test)]
+mod tests;
+
+#[derive(
+    Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error, ErrorCode,
+)]
+#[error_code(namespace = 1, name = "api_mock", http_status = 400)]
 pub enum MockError {
     #[error("there is a unknown error raised")]
+    #[error_code(code = 1, http_status = 500)]
     Unknown,
     #[error("error from remote, {0:?}")]
+    #[error_code(code = 2, http_status = 502)]
     RemoteError(ErrorInfo),
     #[error("the canister name is not allow")]
+    #[error_code(code = 3)]
     InvalidCanisterName,
     #[error("caller not changed since you are not the owner")]
+    #[error_code(code = 5, http_status = 403)]
     OwnerOnly,
     #[error("owner is invalid")]
+    #[error_code(code = 4)]
     InvalidOwner,
     #[error("name is invalid, reason: {reason:?}")]
+    #[error_code(code = 6)]
     InvalidName { reason: String },
     #[error("name is unavailable, reason: {reason:?}")]
+    #[error_code(code = 7)]
     NameUnavailable { reason: String },
     #[error("permission deny")]
+    #[error_code(code = 8, http_status = 403)]
     PermissionDenied,
     #[error("Registration has been taken")]
+    #[error_code(code = 9, http_status = 409)]
     RegistrationHasBeenTaken,
     #[error("Registration is not found")]
+    #[error_code(code = 10, http_status = 404)]
     RegistrationNotFound,
     #[error("Top level named had been set")]
+    #[error_code(code = 11, http_status = 409)]
     TopNameAlreadyExists,
     #[error("registry for {name:?} is not found")]
+    #[error_code(code = 12, http_status = 404)]
     RegistryNotFoundError { name: String },
     #[error("resolver for {name:?} is not found")]
+    #[error_code(code = 13, http_status = 404)]
     ResolverNotFoundError { name: String },
     #[error("operator should not be the same to the owner")]
+    #[error_code(code = 14)]
     OperatorShouldNotBeTheSameToOwner,
     #[error("year must be in rang [{min:?},{max:?})")]
+    #[error_code(code = 15)]
     YearsRangeError { min: u32, max: u32 },
     #[error("invalid resolver key: {key:?}")]
+    #[error_code(code = 16)]
     InvalidResolverKey { key: String },
     #[error("Length of value must be less than {max:?}")]
+    #[error_code(code = 17)]
     ValueMaxLengthError { max: usize },
     #[error("Length of {field:?} must be in range [{min:?}, {max:?})")]
+    #[error_code(code = 18)]
     ValueShouldBeInRangeError {
         field: String,
         min: usize,
         max: usize,
     },
     #[error("You have reached the maximum number of favorites: {max:?}")]
+    #[error_code(code = 19)]
     TooManyFavorites { max: usize },
     #[error("Unauthorized, please login first")]
+    #[error_code(code = 20, http_status = 401)]
     Unauthorized,
     #[error("invalid quota order details")]
+    #[error_code(code = 21)]
     InvalidQuotaOrderDetails,
     #[error("please finish the previous order first")]
+    #[error_code(code = 22, http_status = 409)]
     PendingOrder,
     #[error("quota order is not found")]
+    #[error_code(code = 23, http_status = 404)]
     OrderNotFound,
     #[error("refund failed, please try again later")]
+    #[error_code(code = 24, http_status = 500)]
     RefundFailed,
     #[error("too many operators")]
+    #[error_code(code = 25)]
     OperatorCountExceeded,
     #[error("canister call error, rejected by {rejection_code:?}")]
+    #[error_code(code = 26, http_status = 502)]
     CanisterCallError {
         rejection_code: String,
         message: String,
     },
     #[error("invalid resolver value format for {value:?}, it should be formatted as {format:?}")]
+    #[error_code(code = 27)]
     InvalidResolverValueFormat { value: String, format: String },
     #[error("some operations are processing, please try again later")]
+    #[error_code(code = 28, http_status = 409)]
     Conflict,
     #[error("insufficient quota")]
+    #[error_code(code = 29)]
     InsufficientQuota,
     #[error("it is not allowed to renew the name more than {years:?} years")]
+    #[error_code(code = 30)]
     RenewalYearsError { years: u32 },
     #[error("price changed, please refresh and try again")]
+    #[error_code(code = 31)]
     InvalidApproveAmount,
 }
 
-impl MockError {
-    pub(crate) fn code(&self) -> u32 {
-        match self {
-            MockError::Unknown => 1,
-            MockError::RemoteError(_) => 2,
-            MockError::InvalidCanisterName => 3,
-            MockError::InvalidOwner => 4,
-            MockError::OwnerOnly => 5,
-            MockError::InvalidName { .. } => 6,
-            MockError::NameUnavailable { .. } => 7,
-            MockError::PermissionDenied => 8,
-            MockError::RegistrationHasBeenTaken => 9,
-            MockError::RegistrationNotFound => 10,
-            MockError::TopNameAlreadyExists => 11,
-            MockError::RegistryNotFoundError { .. } => 12,
-            MockError::ResolverNotFoundError { .. } => 13,
-            MockError::OperatorShouldNotBeTheSameToOwner => 14,
-            MockError::YearsRangeError { .. } => 15,
-            MockError::InvalidResolverKey { .. } => 16,
-            MockError::ValueMaxLengthError { .. } => 17,
-            MockError::ValueShouldBeInRangeError { .. } => 18,
-            MockError::TooManyFavorites { .. } => 19,
-            MockError::Unauthorized => 20,
-            MockError::InvalidQuotaOrderDetails => 21,
-            MockError::PendingOrder => 22,
-            MockError::OrderNotFound => 23,
-            MockError::RefundFailed => 24,
-            MockError::OperatorCountExceeded => 25,
-            MockError::CanisterCallError { .. } => 26,
-            MockError::InvalidResolverValueFormat { .. } => 27,
-            MockError::Conflict => 28,
-            MockError::InsufficientQuota => 29,
-            MockError::RenewalYearsError { .. } => 30,
-            MockError::InvalidApproveAmount => 31,
-        }
-    }
-}
-
 /// Error information
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize)]
 pub struct ErrorInfo {
@@ -121,6 +126,10 @@ pub struct ErrorInfo {
     pub code: u32,
     /// Error message
     pub message: String,
+    /// Fields of the error variant
+    pub details: Option<BTreeMap<String, String>>,
+    /// Id to find the error in the canister logs
+    pub trace_id: Option<String>,
 }
 
 impl Display for ErrorInfo {
@@ -130,9 +139,59 @@ impl Display for ErrorInfo {
 }
 
 pub fn get_error_code(error: MockError) -> ErrorInfo {
-    ErrorInfo {
-        code: error.code(),
-        message: error.to_string(),
+    common::errors::ErrorInfo::from_error(&error).into()
+}
+
+/// Chinese messages of the errors users run into, registered in `init` and `post_upgrade`.
+pub fn register_error_messages() {
+    register_messages(
+        "zh",
+        &[
+            (MockError::Unknown.code(), "发生未知错误"),
+            (MockError::InvalidOwner.code(), "所有者无效"),
+            (MockError::OwnerOnly.code(), "只有所有者可以操作"),
+            (
+                MockError::InvalidName {
+                    reason: String::new(),
+                }
+                .code(),
+                "名称无效，原因：{reason}",
+            ),
+            (MockError::PermissionDenied.code(), "没有权限"),
+            (MockError::RegistrationNotFound.code(), "未找到该注册"),
+            (MockError::Unauthorized.code(), "未授权，请先登录"),
+        ],
+    );
+}
+
+impl ErrorInfo {
+    /// Replaces the message with the template of `locale`, see [common::errors::ErrorInfo::localize].
+    pub fn localize(self, locale: Option<&str>) -> Self {
+        common::errors::ErrorInfo::from(self)
+            .localize(locale)
+            .into()
+    }
+}
+
+impl From<ErrorInfo> for common::errors::ErrorInfo {
+    fn from(info: ErrorInfo) -> Self {
+        Self {
+            code: info.code,
+            message: info.message,
+            details: info.details,
+            trace_id: info.trace_id,
+        }
+    }
+}
+
+impl From<common::errors::ErrorInfo> for ErrorInfo {
+    fn from(info: common::errors::ErrorInfo) -> Self {
+        Self {
+            code: info.code,
+            message: info.message,
+            details: info.details,
+            trace_id: info.trace_id,
+        }
     }
 }
 
@@ -146,6 +205,35 @@ impl From<MockError> for ErrorInfo {
     }
 }
 
+impl From<MockError> for common::errors::ErrorInfo {
+    fn from(error: MockError) -> Self {
+        common::errors::ErrorInfo::from_error(&error)
+    }
+}
+
+impl From<MockError> for HttpResponse {
+    fn from(error: MockError) -> Self {
+        HttpResponse::from_error(&error)
+    }
+}
+
+impl From<CommonError> for MockError {
+    fn from(error: CommonError) -> Self {
+        match error {
+            CommonError::StableCollectionError(StableCollectionError::ValueTooLarge {
+                max_size,
+                ..
+            }) => MockError::ValueMaxLengthError {
+                max: max_size as usize,
+            },
+            error => {
+                error!("unexpected common error: {}", error);
+                MockError::Unknown
+            }
+        }
+    }
+}
+
 impl From<ErrorInfo> for MockError {
     fn from(error: ErrorInfo) -> Self {
         MockError::RemoteError(error)
@@ -167,4 +255,11 @@ impl BooleanActorResponse {
             Err(err) => BooleanActorResponse::Err(err.into()),
         }
     }
+
+    pub fn localize(self, locale: Option<&str>) -> Self {
+        match self {
+            BooleanActorResponse::Err(err) => BooleanActorResponse::Err(err.localize(locale)),
+            ok => ok,
+        }
+    }
 }
@@ -0,0 +1,62 @@
+use common::errors::{merge_error_catalogs, CommonError, ERROR_NAMESPACE_SIZE};
+use rstest::*;
+
+use super::*;
+
+#[rstest]
+fn test_mock_error_codes_are_namespaced() {
+    assert_eq!(MockError::Unknown.code(), ERROR_NAMESPACE_SIZE + 1);
+    assert_eq!(
+        get_error_code(MockError::PermissionDenied).code,
+        ERROR_NAMESPACE_SIZE + 8
+    );
+}
+
+#[rstest]
+fn test_error_codes_are_unique() {
+    let catalog = merge_error_catalogs(vec![CommonError::catalog(), MockError::catalog()]);
+    assert_eq!(
+        catalog.len(),
+        CommonError::catalog().len() + MockError::catalog().len()
+    );
+}
+
+#[rstest]
+fn test_error_info_details() {
+    let info = get_error_code(MockError::YearsRangeError { min: 1, max: 10 });
+    assert_eq!(
+        info.details,
+        Some(BTreeMap::from([
+            ("max".to_string(), "10".to_string()),
+            ("min".to_string(), "1".to_string()),
+        ]))
+    );
+    assert!(info.trace_id.is_some());
+    assert_eq!(get_error_code(MockError::Unknown).details, None);
+}
+
+#[rstest]
+#[case(MockError::Unknown, 500)]
+#[case(MockError::InvalidOwner, 400)]
+#[case(MockError::OwnerOnly, 403)]
+#[case(MockError::RegistrationNotFound, 404)]
+#[case(MockError::Conflict, 409)]
+fn test_mock_error_http_status(#[case] error: MockError, #[case] status: u16) {
+    assert_eq!(HttpResponse::from(error).status_code, status);
+}
+
+#[rstest]
+#[case(Some("zh-CN"), "名称无效，原因：too long")]
+#[case(Some("fr"), "name is invalid, reason: \"too long\"")]
+#[case(None, "name is invalid, reason: \"too long\"")]
+fn test_localized_response(#[case] locale: Option<&str>, #[case] expected: &str) {
+    register_error_messages();
+    let response = BooleanActorResponse::new(Err(MockError::InvalidName {
+        reason: "too long".to_string(),
+    }))
+    .localize(locale);
+    match response {
+        BooleanActorResponse::Err(err) => assert_eq!(err.message, expected),
+        BooleanActorResponse::Ok(_) => panic!("expected an error"),
+    }
+}
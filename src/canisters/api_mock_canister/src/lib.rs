@@ -1,4 +1,5 @@
 mod actor;
 mod errors;
 mod mock_utils;
+mod quota;
 mod state;
@@ -0,0 +1,126 @@
+use crate::errors::{MockError, ServiceResult};
+use crate::state::{QuotaMap, State, STATE};
+use candid::{CandidType, Deserialize, Principal};
+use common::batch::{execute_batch, BatchOperation, BatchOutput, Undo};
+
+#[cfg(test)]
+mod tests;
+
+/// Quota of names by length, e.g. `LenGte(7)` for names of 7 or more characters.
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+pub enum QuotaType {
+    LenEq(u8),
+    LenGte(u8),
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct TransferQuotaDetails {
+    pub to: Principal,
+    pub quota_type: QuotaType,
+    pub diff: u32,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone)]
+pub struct BatchTransferRequest {
+    pub items: Vec<TransferQuotaDetails>,
+}
+
+common::actor_response!(pub BatchTransferQuotaResponse(BatchOutput<u32>));
+
+pub fn get_quota(owner: &Principal, quota_type: QuotaType) -> u32 {
+    STATE.with(|s| s.quotas.borrow().get(&(*owner, quota_type)).unwrap_or(0))
+}
+
+/// Adds `diff` to the quota of `owner` and returns the new quota.
+pub fn add_quota(owner: Principal, quota_type: QuotaType, diff: u32) -> ServiceResult<u32> {
+    STATE.with(|s| {
+        let mut quotas = s.quotas.borrow_mut();
+        let quota = quotas
+            .get(&(owner, quota_type))
+            .unwrap_or(0)
+            .checked_add(diff)
+            .ok_or(MockError::InvalidQuotaOrderDetails)?;
+        set_quota(&mut quotas, owner, quota_type, quota)?;
+        Ok(quota)
+    })
+}
+
+fn set_quota(
+    quotas: &mut QuotaMap,
+    owner: Principal,
+    quota_type: QuotaType,
+    quota: u32,
+) -> ServiceResult<()> {
+    if quota == 0 {
+        quotas.remove(&(owner, quota_type));
+    } else {
+        quotas.insert((owner, quota_type), quota)?;
+    }
+    Ok(())
+}
+
+/// Transfers quota of `from`, an operation of [transfer_quotas].
+struct TransferQuota {
+    from: Principal,
+    details: TransferQuotaDetails,
+}
+
+impl BatchOperation<State> for TransferQuota {
+    /// Remaining quota of `from`.
+    type Output = u32;
+    type Error = MockError;
+
+    fn validate(&self, _state: &State) -> ServiceResult<()> {
+        if self.details.diff == 0 {
+            return Err(MockError::InvalidQuotaOrderDetails);
+        }
+        if self.details.to == Principal::anonymous() || self.details.to == self.from {
+            return Err(MockError::InvalidOwner);
+        }
+        Ok(())
+    }
+
+    fn apply(&self, state: &State) -> ServiceResult<(u32, Undo<State>)> {
+        let (from, to, quota_type) = (self.from, self.details.to, self.details.quota_type);
+        let mut quotas = state.quotas.borrow_mut();
+        let from_quota = quotas.get(&(from, quota_type)).unwrap_or(0);
+        let to_quota = quotas.get(&(to, quota_type)).unwrap_or(0);
+        // the quota of `from` may have been spent by a previous operation of the batch
+        if from_quota < self.details.diff {
+            return Err(MockError::InsufficientQuota);
+        }
+        let new_to_quota = to_quota
+            .checked_add(self.details.diff)
+            .ok_or(MockError::InvalidQuotaOrderDetails)?;
+        set_quota(
+            &mut quotas,
+            from,
+            quota_type,
+            from_quota - self.details.diff,
+        )?;
+        set_quota(&mut quotas, to, quota_type, new_to_quota)?;
+
+        let undo: Undo<State> = Box::new(move |state: &State| {
+            let mut quotas = state.quotas.borrow_mut();
+            set_quota(&mut quotas, from, quota_type, from_quota).expect("failed to restore quota");
+            set_quota(&mut quotas, to, quota_type, to_quota).expect("failed to restore quota");
+        });
+        Ok((from_quota - self.details.diff, undo))
+    }
+}
+
+/// Transfers all quotas of `request` from `caller`, or none of them if one of them fails.
+pub fn transfer_quotas(
+    caller: &Principal,
+    request: BatchTransferRequest,
+) -> common::errors::ServiceResult<BatchOutput<u32>> {
+    let operations: Vec<TransferQuota> = request
+        .items
+        .into_iter()
+        .map(|details| TransferQuota {
+            from: *caller,
+            details,
+        })
+        .collect();
+    STATE.with(|state| execute_batch(state, operations))
+}
@@ -0,0 +1,81 @@
+use common::batch::BatchItemResult;
+use common::errors::ErrorCode;
+use rstest::*;
+use test_common::ic_api::init_test;
+
+use super::*;
+
+const QUOTA_TYPE: QuotaType = QuotaType::LenGte(7);
+
+fn principal(id: u8) -> Principal {
+    Principal::from_slice(&[id])
+}
+
+fn transfer(to: u8, diff: u32) -> TransferQuotaDetails {
+    TransferQuotaDetails {
+        to: principal(to),
+        quota_type: QUOTA_TYPE,
+        diff,
+    }
+}
+
+#[fixture]
+fn owner() -> Principal {
+    init_test();
+    add_quota(principal(1), QUOTA_TYPE, 3).unwrap();
+    principal(1)
+}
+
+#[rstest]
+fn test_transfer_quotas(owner: Principal) {
+    let request = BatchTransferRequest {
+        items: vec![transfer(2, 1), transfer(3, 2)],
+    };
+    let output = transfer_quotas(&owner, request).unwrap();
+    assert_eq!(
+        output,
+        BatchOutput {
+            committed: true,
+            items: vec![BatchItemResult::Ok(2), BatchItemResult::Ok(0)],
+        }
+    );
+    assert_eq!(get_quota(&owner, QUOTA_TYPE), 0);
+    assert_eq!(get_quota(&principal(2), QUOTA_TYPE), 1);
+    assert_eq!(get_quota(&principal(3), QUOTA_TYPE), 2);
+}
+
+#[rstest]
+fn test_failed_transfer_rolls_back_batch(owner: Principal) {
+    let request = BatchTransferRequest {
+        items: vec![transfer(2, 2), transfer(3, 2)],
+    };
+    let output = transfer_quotas(&owner, request).unwrap();
+    assert!(!output.committed);
+    assert_eq!(output.items[0], BatchItemResult::NotApplied);
+    assert!(matches!(
+        &output.items[1],
+        BatchItemResult::Err(err) if err.code == MockError::InsufficientQuota.code()
+    ));
+    assert_eq!(get_quota(&owner, QUOTA_TYPE), 3);
+    assert_eq!(get_quota(&principal(2), QUOTA_TYPE), 0);
+    assert_eq!(get_quota(&principal(3), QUOTA_TYPE), 0);
+}
+
+#[rstest]
+fn test_invalid_transfer_rejects_batch(owner: Principal) {
+    let request = BatchTransferRequest {
+        items: vec![transfer(2, 1), transfer(3, 0), transfer(1, 1)],
+    };
+    let output = transfer_quotas(&owner, request).unwrap();
+    assert!(!output.committed);
+    assert_eq!(output.items[0], BatchItemResult::NotApplied);
+    assert!(matches!(
+        &output.items[1],
+        BatchItemResult::Err(err) if err.code == MockError::InvalidQuotaOrderDetails.code()
+    ));
+    assert!(matches!(
+        &output.items[2],
+        BatchItemResult::Err(err) if err.code == MockError::InvalidOwner.code()
+    ));
+    assert_eq!(get_quota(&owner, QUOTA_TYPE), 3);
+}
@@ -1,12 +1,20 @@
-use crate::errors::{MockError, ServiceResult};
+use crate::errors::{register_error_messages, MockError, ServiceResult};
 use crate::mock_utils::{normalize_name, FirstLevelName, NameParseResult};
+use crate::quota::QuotaType;
 use candid::Principal;
 use common::ic_logger::ICLogger;
 use common::named_canister_ids::{ensure_current_canister_id_match, CanisterNames};
+use common::state::collections::{StableCollectionData, StableMap};
+use common::state::migration::migrate_args;
+use common::state::{StableState, StateDecodeError, StateMigrations};
 use common::types::AuthPrincipal;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::sync::Once;
+
+#[cfg(test)]
+mod tests;
+
 thread_local! {
     pub static STATE : State = State::default();
 }
@@ -17,15 +25,54 @@ pub(crate) fn canister_module_init() {
     INIT.call_once(|| {
         ICLogger::init("MockSampleCanister");
     });
+    register_error_messages();
     ensure_current_canister_id_match(CanisterNames::MockSampleCanister);
 }
 
-#[derive(Default)]
+const REGISTRIES_MEMORY_ID: u8 = 0;
+const APPROVALS_MEMORY_ID: u8 = 1;
+const QUOTAS_MEMORY_ID: u8 = 2;
+
+pub(crate) type QuotaMap = StableMap<(Principal, QuotaType), u32, QUOTAS_MEMORY_ID>;
+
+#[derive(Default, StableState)]
+#[stable(version = 3, migrations = state_migrations)]
 pub struct State {
     // NOTE: When adding new persistent fields here, ensure that these fields
     // are being persisted in the `replace` method below.
-    pub(crate) registries: RefCell<HashMap<String, Principal>>,
-    pub(crate) approvals: RefCell<HashMap<String, Principal>>,
+    pub(crate) registries: RefCell<StableMap<String, Principal, REGISTRIES_MEMORY_ID>>,
+    pub(crate) approvals: RefCell<StableMap<String, Principal, APPROVALS_MEMORY_ID>>,
+    #[stable(since = 3)]
+    pub(crate) quotas: RefCell<QuotaMap>,
+}
+
+fn state_migrations() -> StateMigrations {
+    StateMigrations::new().register(1, move_names_to_stable_maps)
+}
+
+/// Version 2 keeps the registries and approvals in stable maps instead of heap maps.
+fn move_names_to_stable_maps(bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+    type Names = BTreeMap<String, Principal>;
+    migrate_args(bytes, |(registries, approvals): (Names, Names)| {
+        (
+            StableCollectionData::with_entries(
+                REGISTRIES_MEMORY_ID,
+                registries.into_iter().collect(),
+            ),
+            StableCollectionData::with_entries(
+                APPROVALS_MEMORY_ID,
+                approvals.into_iter().collect(),
+            ),
+        )
+    })
+}
+
+impl State {
+    pub fn replace(&self, new_state: State) {
+        self.registries.replace(new_state.registries.take());
+        self.approvals.replace(new_state.approvals.take());
+        self.quotas.replace(new_state.quotas.take());
+    }
 }
 
 pub fn is_name_owner(name: &FirstLevelName, caller: &Principal) -> ServiceResult<Principal> {
@@ -35,8 +82,7 @@ pub fn is_name_owner(name: &FirstLevelName, caller: &Principal) -> ServiceResult
         if registration.is_none() {
             return Err(MockError::RegistrationNotFound);
         }
-        let registration = registration.unwrap();
-        let owner = registration.clone();
+        let owner = registration.unwrap();
 
         if !owner.eq(caller) {
             return Err(MockError::PermissionDenied);
@@ -76,7 +122,7 @@ pub fn approve(caller: &Principal, name: &str, to: Principal) -> ServiceResult<b
     must_not_anonymous(&caller)?;
 
     is_name_owner(&name, &caller)?;
-    set_approval(&name, &to);
+    set_approval(&name, &to)?;
     Ok(true)
 }
 
@@ -87,21 +133,22 @@ pub fn must_not_anonymous(caller: &Principal) -> ServiceResult<AuthPrincipal> {
     Ok(AuthPrincipal(caller.clone()))
 }
 
-pub fn set_approval(name: &FirstLevelName, approved_to: &Principal) {
+pub fn set_approval(name: &FirstLevelName, approved_to: &Principal) -> ServiceResult<()> {
     STATE.with(|s| {
         let mut approvals = s.approvals.borrow_mut();
         if approved_to == &Principal::anonymous() {
             approvals.remove(name.0.get_name());
         } else {
-            approvals.insert(name.to_string(), approved_to.clone());
+            approvals.insert(name.to_string(), approved_to.clone())?;
         }
-    });
+        Ok(())
+    })
 }
 
 pub fn is_approved_to(name: &str, approved_to: &Principal) -> bool {
     STATE.with(|s| {
         let approvals = s.approvals.borrow();
-        let approval = approvals.get(name);
+        let approval = approvals.get(&name.to_string());
         if approval.is_none() {
             return false;
         }
@@ -113,6 +160,6 @@ pub fn is_approved_to(name: &str, approved_to: &Principal) -> bool {
 pub fn remove_approval(name: &str) {
     STATE.with(|s| {
         let mut approvals = s.approvals.borrow_mut();
-        approvals.remove(name);
+        approvals.remove(&name.to_string());
     });
 }
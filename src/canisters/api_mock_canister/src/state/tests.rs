@@ -0,0 +1,66 @@
+use rstest::*;
+use test_common::golden::assert_state_compatible;
+use test_common::ic_api::init_test;
+
+use super::*;
+
+const GOLDEN_STATE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/golden/state");
+
+fn sample_state() -> State {
+    let state = State::default();
+    state
+        .registries
+        .borrow_mut()
+        .insert("test".to_string(), Principal::anonymous())
+        .unwrap();
+    state
+        .approvals
+        .borrow_mut()
+        .insert("test".to_string(), Principal::anonymous())
+        .unwrap();
+    state
+        .quotas
+        .borrow_mut()
+        .insert((Principal::anonymous(), QuotaType::LenGte(7)), 3)
+        .unwrap();
+    state
+}
+
+fn assert_sample_names(state: &State) {
+    let name = "test".to_string();
+    assert_eq!(
+        state.registries.borrow().get(&name),
+        Some(Principal::anonymous())
+    );
+    assert_eq!(
+        state.approvals.borrow().get(&name),
+        Some(Principal::anonymous())
+    );
+}
+
+#[rstest]
+fn test_state_golden_files() {
+    init_test();
+    let states = assert_state_compatible(GOLDEN_STATE_DIR, &[("sample", sample_state())]);
+    // the decoded states share the memories of the stable maps, the last decoded one is current
+    let (_, state) = states.last().unwrap();
+    assert_sample_names(state);
+    assert_eq!(
+        state
+            .quotas
+            .borrow()
+            .get(&(Principal::anonymous(), QuotaType::LenGte(7))),
+        Some(3)
+    );
+}
+
+#[rstest]
+fn test_heap_maps_are_moved_to_stable_maps() {
+    init_test();
+    let bytes = std::fs::read(format!("{}/sample.v1.state", GOLDEN_STATE_DIR)).unwrap();
+    let state = State::decode(bytes).unwrap();
+    assert_sample_names(&state);
+    assert_eq!(state.registries.borrow().len(), 1);
+    assert_eq!(state.approvals.borrow().len(), 1);
+    assert!(state.quotas.borrow().is_empty());
+}
@@ -0,0 +1,109 @@
+use candid::{CandidType, Deserialize};
+
+use crate::constants::{BATCH_MAX_OPERATIONS, BATCH_MIN_OPERATIONS};
+use crate::errors::{CommonError, ErrorInfo, ServiceResult};
+
+#[cfg(test)]
+mod tests;
+
+/// Undoes the changes of one applied operation.
+pub type Undo<S> = Box<dyn FnOnce(&S)>;
+
+/// An operation of a batch executed by [execute_batch] against state `S`.
+///
+/// Operations fail with the errors of their canister, returned to the client as [ErrorInfo].
+pub trait BatchOperation<S> {
+    type Output;
+    type Error: Into<ErrorInfo>;
+
+    /// Checks the operation against the state before any operation of the batch is applied.
+    fn validate(&self, state: &S) -> Result<(), Self::Error>;
+
+    /// Applies the operation and returns its output together with the action which undoes it.
+    /// The state must be left unchanged when an error is returned.
+    fn apply(&self, state: &S) -> Result<(Self::Output, Undo<S>), Self::Error>;
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub enum BatchItemResult<T> {
+    Ok(T),
+    Err(ErrorInfo),
+    /// The operation is valid, but the batch was not committed because another operation failed.
+    NotApplied,
+}
+
+/// Results of a batch.
+///
+/// `export_service!` numbers the candid names of generic types, return the output in a response
+/// declared with [crate::actor_response] so that the endpoint has a stable candid type:
+///
+/// ```ignore
+/// common::actor_response!(pub BatchTransferResponse(BatchOutput<u64>));
+/// ```
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct BatchOutput<T> {
+    /// Whether all operations were applied, otherwise none of them is.
+    pub committed: bool,
+    /// Results in the order of the operations.
+    pub items: Vec<BatchItemResult<T>>,
+}
+
+fn not_committed<T, E: Into<ErrorInfo>>(count: usize, failed: Vec<(usize, E)>) -> BatchOutput<T> {
+    let mut items: Vec<BatchItemResult<T>> =
+        (0..count).map(|_| BatchItemResult::NotApplied).collect();
+    for (index, err) in failed {
+        items[index] = BatchItemResult::Err(err.into());
+    }
+    BatchOutput {
+        committed: false,
+        items,
+    }
+}
+
+/// Applies all `operations` or none of them.
+///
+/// All operations are validated first, then applied in order. When an operation fails to apply,
+/// the operations applied before it are undone in reverse order. Operations must not await, so that
+/// no other message observes the state in between.
+pub fn execute_batch<S, O>(state: &S, operations: Vec<O>) -> ServiceResult<BatchOutput<O::Output>>
+where
+    O: BatchOperation<S>,
+{
+    if operations.len() < BATCH_MIN_OPERATIONS || operations.len() > BATCH_MAX_OPERATIONS {
+        return Err(CommonError::ValueShouldBeInRangeError {
+            field: "operations".to_string(),
+            min: BATCH_MIN_OPERATIONS,
+            max: BATCH_MAX_OPERATIONS,
+        });
+    }
+
+    let invalid: Vec<(usize, O::Error)> = operations
+        .iter()
+        .enumerate()
+        .filter_map(|(index, operation)| operation.validate(state).err().map(|err| (index, err)))
+        .collect();
+    if !invalid.is_empty() {
+        return Ok(not_committed(operations.len(), invalid));
+    }
+
+    let mut outputs = Vec::with_capacity(operations.len());
+    let mut undo_log: Vec<Undo<S>> = Vec::with_capacity(operations.len());
+    for (index, operation) in operations.iter().enumerate() {
+        match operation.apply(state) {
+            Ok((output, undo)) => {
+                outputs.push(output);
+                undo_log.push(undo);
+            }
+            Err(err) => {
+                for undo in undo_log.into_iter().rev() {
+                    undo(state);
+                }
+                return Ok(not_committed(operations.len(), vec![(index, err)]));
+            }
+        }
+    }
+    Ok(BatchOutput {
+        committed: true,
+        items: outputs.into_iter().map(BatchItemResult::Ok).collect(),
+    })
+}
@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use rstest::*;
+
+use super::*;
+use crate::test_common::test::init_test;
+
+struct Ledger {
+    balances: RefCell<HashMap<String, u64>>,
+}
+
+impl Ledger {
+    fn balance(&self, account: &str) -> u64 {
+        self.balances.borrow().get(account).cloned().unwrap_or(0)
+    }
+}
+
+struct Transfer {
+    from: &'static str,
+    to: &'static str,
+    amount: u64,
+}
+
+impl BatchOperation<Ledger> for Transfer {
+    type Output = u64;
+    type Error = CommonError;
+
+    fn validate(&self, state: &Ledger) -> ServiceResult<()> {
+        if self.amount == 0 || !state.balances.borrow().contains_key(self.from) {
+            return Err(CommonError::PermissionDenied);
+        }
+        Ok(())
+    }
+
+    fn apply(&self, state: &Ledger) -> ServiceResult<(u64, Undo<Ledger>)> {
+        let from_balance = state.balance(self.from);
+        if from_balance < self.amount {
+            return Err(CommonError::Unknown {
+                detail: "insufficient balance".to_string(),
+            });
+        }
+        let to_balance = state.balance(self.to);
+        let mut balances = state.balances.borrow_mut();
+        balances.insert(self.from.to_string(), from_balance - self.amount);
+        balances.insert(self.to.to_string(), to_balance + self.amount);
+
+        let (from, to) = (self.from, self.to);
+        let undo: Undo<Ledger> = Box::new(move |state: &Ledger| {
+            let mut balances = state.balances.borrow_mut();
+            balances.insert(from.to_string(), from_balance);
+            balances.insert(to.to_string(), to_balance);
+        });
+        Ok((from_balance - self.amount, undo))
+    }
+}
+
+fn transfer(from: &'static str, to: &'static str, amount: u64) -> Transfer {
+    Transfer { from, to, amount }
+}
+
+#[fixture]
+fn ledger() -> Ledger {
+    init_test();
+    Ledger {
+        balances: RefCell::new(HashMap::from([
+            ("alice".to_string(), 100),
+            ("bob".to_string(), 10),
+        ])),
+    }
+}
+
+#[rstest]
+fn test_all_operations_applied(ledger: Ledger) {
+    let output = execute_batch(
+        &ledger,
+        vec![transfer("alice", "bob", 30), transfer("bob", "carol", 40)],
+    )
+    .unwrap();
+    assert_eq!(
+        output,
+        BatchOutput {
+            committed: true,
+            items: vec![BatchItemResult::Ok(70), BatchItemResult::Ok(0)],
+        }
+    );
+    assert_eq!(ledger.balance("alice"), 70);
+    assert_eq!(ledger.balance("bob"), 0);
+    assert_eq!(ledger.balance("carol"), 40);
+}
+
+#[rstest]
+fn test_invalid_operation_rejects_batch(ledger: Ledger) {
+    let output = execute_batch(
+        &ledger,
+        vec![transfer("alice", "bob", 30), transfer("carol", "bob", 1)],
+    )
+    .unwrap();
+    assert!(!output.committed);
+    assert_eq!(output.items[0], BatchItemResult::NotApplied);
+    assert!(matches!(&output.items[1], BatchItemResult::Err(err) if err.code == 4));
+    assert_eq!(ledger.balance("alice"), 100);
+}
+
+#[rstest]
+fn test_failed_operation_rolls_back_applied_operations(ledger: Ledger) {
+    let output = execute_batch(
+        &ledger,
+        vec![
+            transfer("alice", "bob", 30),
+            transfer("bob", "alice", 50),
+            transfer("alice", "bob", 1),
+        ],
+    )
+    .unwrap();
+    assert!(!output.committed);
+    assert_eq!(output.items[0], BatchItemResult::NotApplied);
+    assert!(matches!(output.items[1], BatchItemResult::Err(_)));
+    assert_eq!(output.items[2], BatchItemResult::NotApplied);
+    assert_eq!(ledger.balance("alice"), 100);
+    assert_eq!(ledger.balance("bob"), 10);
+}
+
+#[rstest]
+fn test_batch_size_out_of_range(ledger: Ledger) {
+    let result = execute_batch::<Ledger, Transfer>(&ledger, vec![]);
+    assert_eq!(
+        result,
+        Err(CommonError::ValueShouldBeInRangeError {
+            field: "operations".to_string(),
+            min: BATCH_MIN_OPERATIONS,
+            max: BATCH_MAX_OPERATIONS,
+        })
+    );
+}
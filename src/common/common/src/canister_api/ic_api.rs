@@ -66,4 +66,5 @@ pub trait IICManagementAPI {
     ) -> ActorResult<ECDSAPublicKeyReply>;
     async fn sign_with_ecdsa(&self, sign_request: SignWithECDSA)
         -> ActorResult<SignWithECDSAReply>;
+    async fn raw_rand(&self) -> ActorResult<Vec<u8>>;
 }
@@ -144,4 +144,8 @@ impl IICManagementAPI for ICManagementAPI {
         )
         .await
     }
+
+    async fn raw_rand(&self) -> ActorResult<Vec<u8>> {
+        call_canister_as_result_no_logging(CanisterNames::ICManagement, "raw_rand", ()).await
+    }
 }
@@ -9,6 +9,7 @@ use serde::Deserialize;
 
 pub use ic_api::*;
 
+use crate::canister_api::reject::RejectCode;
 use crate::errors::{ActorResult, CommonError, ErrorInfo};
 use crate::named_canister_ids::{get_named_canister_id, CanisterNames};
 use crate::types::ic_ledger_types::{Subaccount, TransferArgs, TransferResult};
@@ -16,6 +17,7 @@ use crate::types::ic_management_types::*;
 
 pub mod ic_api;
 pub mod ic_impl;
+pub mod reject;
 
 async fn call_core<T, TResult>(
     canister_name: CanisterNames,
@@ -35,14 +37,14 @@ where
         call(canister_id.0, method, args)
             .await
             .map_err(|(code, message)| {
-                let code_string = format!("{:?}", code);
+                let code = RejectCode::from(code);
                 error!(
                     "{:?}::{} failed with code {}: {}",
-                    canister_name, method, code_string, message
+                    canister_name, method, code, message
                 );
                 CommonError::CanisterCallError {
                     message,
-                    rejection_code: code_string,
+                    rejection_code: code,
                 }
             })?;
 
@@ -120,14 +122,14 @@ where
     let (call_res,): (TResult,) = call_with_payment(canister_id.0, method, args, cycles)
         .await
         .map_err(|(code, message)| {
-            let code_string = format!("{:?}", code);
+            let code = RejectCode::from(code);
             error!(
                 "{:?}::{} failed with code {}: {}",
-                canister_name, method, code_string, message
+                canister_name, method, code, message
             );
             CommonError::CanisterCallError {
                 message,
-                rejection_code: code_string,
+                rejection_code: code,
             }
         })?;
 
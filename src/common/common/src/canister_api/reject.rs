@@ -0,0 +1,88 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use candid::{CandidType, Deserialize};
+use ic_cdk::api::call::RejectionCode;
+
+#[cfg(test)]
+mod tests;
+
+/// Reject code of a failed canister call, see [RejectionCode].
+///
+/// Declared here rather than using [RejectionCode] in [crate::errors::CommonError] so that the
+/// candid type of errors does not change with ic-cdk.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, CandidType, Deserialize)]
+pub enum RejectCode {
+    NoError,
+    SysFatal,
+    SysTransient,
+    DestinationInvalid,
+    CanisterReject,
+    CanisterError,
+    Unknown,
+}
+
+/// How a failed call affected the callee, to decide whether it can be retried.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, CandidType, Deserialize)]
+pub enum CallFailureKind {
+    /// The call was not executed and may succeed later, it is safe to retry.
+    Transient,
+    /// The call was not executed, retrying will fail the same way.
+    Permanent,
+    /// The call may have been executed, e.g. the callee trapped or rejected the call after
+    /// committing state changes at an `await`, or the reply could not be decoded. Check the callee
+    /// state before retrying.
+    UnknownOutcome,
+}
+
+impl RejectCode {
+    pub fn failure_kind(&self) -> CallFailureKind {
+        match self {
+            RejectCode::SysTransient => CallFailureKind::Transient,
+            RejectCode::SysFatal | RejectCode::DestinationInvalid => CallFailureKind::Permanent,
+            // an explicit reject may follow state changes committed at an await of the callee
+            RejectCode::CanisterReject
+            | RejectCode::CanisterError
+            | RejectCode::NoError
+            | RejectCode::Unknown => CallFailureKind::UnknownOutcome,
+        }
+    }
+}
+
+impl From<RejectionCode> for RejectCode {
+    fn from(code: RejectionCode) -> Self {
+        match code {
+            RejectionCode::NoError => RejectCode::NoError,
+            RejectionCode::SysFatal => RejectCode::SysFatal,
+            RejectionCode::SysTransient => RejectCode::SysTransient,
+            RejectionCode::DestinationInvalid => RejectCode::DestinationInvalid,
+            RejectionCode::CanisterReject => RejectCode::CanisterReject,
+            RejectionCode::CanisterError => RejectCode::CanisterError,
+            RejectionCode::Unknown => RejectCode::Unknown,
+        }
+    }
+}
+
+impl Display for RejectCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for RejectCode {
+    type Err = String;
+
+    /// Parses the [Display] form, e.g. the `rejection_code` detail of an [crate::errors::ErrorInfo].
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "NoError" => Ok(RejectCode::NoError),
+            "SysFatal" => Ok(RejectCode::SysFatal),
+            "SysTransient" => Ok(RejectCode::SysTransient),
+            "DestinationInvalid" => Ok(RejectCode::DestinationInvalid),
+            "CanisterReject" => Ok(RejectCode::CanisterReject),
+            "CanisterError" => Ok(RejectCode::CanisterError),
+            "Unknown" => Ok(RejectCode::Unknown),
+            _ => Err(format!("unknown reject code {}", value)),
+        }
+    }
+}
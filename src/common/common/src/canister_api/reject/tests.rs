@@ -0,0 +1,101 @@
+use rstest::*;
+
+use super::*;
+use crate::errors::{CommonError, ErrorInfo};
+
+#[rstest]
+#[case(RejectionCode::SysTransient, CallFailureKind::Transient)]
+#[case(RejectionCode::SysFatal, CallFailureKind::Permanent)]
+#[case(RejectionCode::DestinationInvalid, CallFailureKind::Permanent)]
+#[case(RejectionCode::CanisterReject, CallFailureKind::UnknownOutcome)]
+#[case(RejectionCode::CanisterError, CallFailureKind::UnknownOutcome)]
+#[case(RejectionCode::Unknown, CallFailureKind::UnknownOutcome)]
+fn test_failure_kind(#[case] code: RejectionCode, #[case] kind: CallFailureKind) {
+    assert_eq!(RejectCode::from(code).failure_kind(), kind);
+}
+
+fn call_error(code: RejectCode) -> CommonError {
+    CommonError::CanisterCallError {
+        message: "".to_string(),
+        rejection_code: code,
+    }
+}
+
+#[rstest]
+fn test_common_error_retry_helpers() {
+    let transient = call_error(RejectCode::SysTransient);
+    assert!(transient.is_retryable());
+    assert!(!transient.is_outcome_unknown());
+
+    let trapped = call_error(RejectCode::CanisterError);
+    assert!(!trapped.is_retryable());
+    assert!(trapped.is_outcome_unknown());
+
+    let rejected = call_error(RejectCode::CanisterReject);
+    assert_eq!(
+        rejected.call_failure_kind(),
+        Some(CallFailureKind::UnknownOutcome)
+    );
+    assert!(!rejected.is_retryable());
+
+    let invalid = call_error(RejectCode::DestinationInvalid);
+    assert_eq!(
+        invalid.call_failure_kind(),
+        Some(CallFailureKind::Permanent)
+    );
+
+    assert_eq!(CommonError::PermissionDenied.call_failure_kind(), None);
+    assert!(!CommonError::PermissionDenied.is_retryable());
+}
+
+#[rstest]
+fn test_reject_code_in_error_details() {
+    let info = crate::errors::get_error_code(call_error(RejectCode::SysTransient));
+    assert_eq!(
+        info.message,
+        "canister call error, rejected by SysTransient"
+    );
+    assert_eq!(
+        info.details.unwrap().get("rejection_code"),
+        Some(&"SysTransient".to_string())
+    );
+}
+
+#[rstest]
+#[case(RejectCode::SysTransient, true, false)]
+#[case(RejectCode::CanisterError, false, true)]
+#[case(RejectCode::SysFatal, false, false)]
+fn test_error_info_retry_helpers(
+    #[case] code: RejectCode,
+    #[case] retryable: bool,
+    #[case] outcome_unknown: bool,
+) {
+    let info = ErrorInfo::from(call_error(code));
+    assert_eq!(info.rejection_code(), Some(code));
+    assert_eq!(info.call_failure_kind(), Some(code.failure_kind()));
+    assert_eq!(info.is_retryable(), retryable);
+    assert_eq!(info.is_outcome_unknown(), outcome_unknown);
+}
+
+#[rstest]
+fn test_error_info_of_other_errors_has_no_failure_kind() {
+    let info = ErrorInfo::from(CommonError::PermissionDenied);
+    assert_eq!(info.call_failure_kind(), None);
+    assert!(!info.is_retryable());
+
+    let mut info = ErrorInfo::from(call_error(RejectCode::SysTransient));
+    info.details = None;
+    assert_eq!(info.call_failure_kind(), None);
+}
+
+#[rstest]
+#[case(RejectCode::NoError)]
+#[case(RejectCode::SysFatal)]
+#[case(RejectCode::SysTransient)]
+#[case(RejectCode::DestinationInvalid)]
+#[case(RejectCode::CanisterReject)]
+#[case(RejectCode::CanisterError)]
+#[case(RejectCode::Unknown)]
+fn test_parse_reject_code(#[case] code: RejectCode) {
+    assert_eq!(code.to_string().parse::<RejectCode>(), Ok(code));
+}
@@ -1,3 +1,7 @@
+#[cfg(feature = "state_encryption")]
+use crate::dto::parse_state_exporter_public_key;
+#[cfg(feature = "state_encryption")]
+use crate::errors::ServiceResult;
 use crate::named_canister_ids::{CanisterNames, DEV_NAMED_CANISTER_IDS};
 use candid::Principal;
 use const_env::from_env;
@@ -9,6 +13,29 @@ pub const PAGE_INPUT_MIN_LIMIT: usize = 1;
 pub const PAGE_INPUT_MAX_LIMIT: usize = 100;
 pub const PAGE_INPUT_MIN_OFFSET: usize = 0;
 pub const PAGE_INPUT_MAX_OFFSET: usize = 10_000;
+pub const PAGE_INPUT_MAX_FILTERS: usize = 10;
+
+pub const BATCH_MIN_OPERATIONS: usize = 1;
+pub const BATCH_MAX_OPERATIONS: usize = 100;
+
+// keep chunks well below the 2 MiB ingress and response limits
+pub const STATE_TRANSFER_CHUNK_SIZE: u64 = 1024 * 1024;
+pub const STATE_TRANSFER_MAX_SESSIONS: usize = 4;
+// 30 minutes
+pub const STATE_TRANSFER_SESSION_TIMEOUT_NS: u64 = 30 * 60 * 1_000_000_000;
+// an import keeps its chunks on the heap until it is finalized, and finalizing copies them once
+pub const STATE_IMPORT_MAX_SIZE: u64 = 128 * 1024 * 1024;
+// bytes all open imports may buffer together, well below the 4 GiB wasm32 heap
+pub const STATE_IMPORT_MAX_BUFFERED_SIZE: u64 = 256 * 1024 * 1024;
+
+// size of http response chunks served by the streaming callback
+pub const HTTP_STREAMING_CHUNK_SIZE: usize = 1024 * 1024;
+
+// decompressing a state export must not produce more than 1 GiB
+pub const STATE_MAX_DECOMPRESSED_SIZE: u64 = 1024 * 1024 * 1024;
+
+// number of state snapshots kept in stable memory, the oldest one is dropped first
+pub const STATE_SNAPSHOT_MAX_COUNT: usize = 3;
 
 pub const ENV_DEV: &str = "dev";
 pub const ENV_STAGING: &str = "staging";
@@ -86,5 +113,20 @@ pub const COMMON_PRINCIPAL_NAME_STATE_EXPORTER: &str = "";
 #[from_env]
 pub const COMMON_PRINCIPAL_NAME_TIMER_TRIGGER: &str = "";
 
+// hex encoded X25519 public key of the state exporter, state exports are encrypted to it when set
+#[cfg(feature = "state_encryption")]
+#[from_env]
+const COMMON_STATE_EXPORTER_PUBLIC_KEY: &str = "";
+
+/// The state exporter public key configured at build time, an invalid key fails the export
+/// instead of trapping.
+#[cfg(feature = "state_encryption")]
+pub fn configured_state_exporter_public_key() -> ServiceResult<Option<[u8; 32]>> {
+    if COMMON_STATE_EXPORTER_PUBLIC_KEY.is_empty() {
+        return Ok(None);
+    }
+    parse_state_exporter_public_key(COMMON_STATE_EXPORTER_PUBLIC_KEY).map(Some)
+}
+
 #[cfg(test)]
 mod tests;
@@ -1,29 +1,103 @@
 use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 
-use std::io::{Read, Write};
-
 use candid::{CandidType, Deserialize, Principal};
-use flate2::read::ZlibDecoder;
-use flate2::write::ZlibEncoder;
-use flate2::Compression;
 
+use crate::actor_response;
 use crate::constants::{
-    PAGE_INPUT_MAX_LIMIT, PAGE_INPUT_MAX_OFFSET, PAGE_INPUT_MIN_LIMIT, PAGE_INPUT_MIN_OFFSET,
+    PAGE_INPUT_MAX_FILTERS, PAGE_INPUT_MAX_LIMIT, PAGE_INPUT_MAX_OFFSET, PAGE_INPUT_MIN_LIMIT,
+    PAGE_INPUT_MIN_OFFSET, STATE_MAX_DECOMPRESSED_SIZE,
+};
+use crate::errors::{ActorResponse, CommonError, ServiceResult};
+use crate::state::transfer::{sha256_hex, StateTransferError};
+use crate::types::TimeInNs;
+
+pub mod codec;
+pub mod cursor;
+
+pub use codec::{compress, decode_zlib, decompress, encode_zlib, CodecError, CompressionAlgorithm};
+pub use cursor::{
+    decode_cursor, encode_cursor, page_btree_map, page_sorted, GetCursorPageInput,
+    GetCursorPageOutput,
 };
-use crate::errors::{CommonError, ErrorInfo, ServiceResult};
 
 #[cfg(test)]
 mod tests;
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct SortSpec {
+    pub field: String,
+    pub direction: SortDirection,
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FilterOperator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    StartsWith,
+}
+
+/// Predicate `field operator value`, the endpoint interprets `value` according to the type of `field`.
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct FilterSpec {
+    pub field: String,
+    pub operator: FilterOperator,
+    pub value: String,
+}
+
+/// Fields an endpoint allows to sort and filter by.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct PageFields {
+    pub sortable: &'static [&'static str],
+    pub filterable: &'static [&'static str],
+}
+
+impl PageFields {
+    /// Neither sorting nor filtering is allowed.
+    pub const NONE: PageFields = PageFields {
+        sortable: &[],
+        filterable: &[],
+    };
+}
+
+/// Offset paging, limited to [PAGE_INPUT_MAX_OFFSET]. Prefer [GetCursorPageInput] for collections
+/// which may grow beyond it or change while being paged.
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct GetPageInput {
     pub offset: usize,
     pub limit: usize,
+    pub sort: Option<SortSpec>,
+    /// Predicates which must all match.
+    pub filters: Option<Vec<FilterSpec>>,
 }
 
 impl GetPageInput {
-    pub fn validate(&self) -> ServiceResult<()> {
+    pub fn new(offset: usize, limit: usize) -> Self {
+        Self {
+            offset,
+            limit,
+            sort: None,
+            filters: None,
+        }
+    }
+
+    pub fn filters(&self) -> &[FilterSpec] {
+        self.filters.as_deref().unwrap_or_default()
+    }
+
+    /// Validates the range of offset and limit, and that sort and filter fields are in `fields`.
+    pub fn validate(&self, fields: &PageFields) -> ServiceResult<()> {
         let max_offset = PAGE_INPUT_MAX_OFFSET;
         let min_offset = PAGE_INPUT_MIN_OFFSET;
         if self.offset > max_offset || self.offset < min_offset {
@@ -42,24 +116,75 @@ impl GetPageInput {
                 max: max_limit,
             });
         }
+        if let Some(sort) = &self.sort {
+            if !fields.sortable.contains(&sort.field.as_str()) {
+                return Err(CommonError::PageFieldNotAllowed {
+                    field: sort.field.clone(),
+                    usage: "sort".to_string(),
+                });
+            }
+        }
+        if self.filters().len() > PAGE_INPUT_MAX_FILTERS {
+            return Err(CommonError::ValueShouldBeInRangeError {
+                field: "filters".to_string(),
+                min: 0,
+                max: PAGE_INPUT_MAX_FILTERS,
+            });
+        }
+        for filter in self.filters() {
+            if !fields.filterable.contains(&filter.field.as_str()) {
+                return Err(CommonError::PageFieldNotAllowed {
+                    field: filter.field.clone(),
+                    usage: "filter".to_string(),
+                });
+            }
+        }
         Ok(())
     }
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct GetPageOutput<T> {
     pub items: Vec<T>,
+    /// Number of items matching the filters of the input, across all pages.
+    pub total: usize,
+    pub has_more: bool,
+    /// The input this page was returned for.
+    pub input: GetPageInput,
 }
 
 impl<T> GetPageOutput<T> {
-    pub fn new(items: Vec<T>) -> Self {
-        Self { items }
+    pub fn new(items: Vec<T>, total: usize, input: GetPageInput) -> Self {
+        Self {
+            has_more: input.offset + items.len() < total,
+            items,
+            total,
+            input,
+        }
+    }
+
+    /// Returns the page of `input` from all sorted and filtered `items`.
+    pub fn from_items(items: Vec<T>, input: GetPageInput) -> Self {
+        let total = items.len();
+        let page = items
+            .into_iter()
+            .skip(input.offset)
+            .take(input.limit)
+            .collect();
+        Self::new(page, total, input)
     }
 }
 
-#[derive(CandidType, Deserialize)]
+/// Response of `get_stats`, declare the response with [actor_response] instead.
+#[deprecated(note = "use `ActorResponse` or a type declared with `actor_response!`")]
+pub type GetStatsResponse<T> = ActorResponse<T>;
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct LoadStateRequest {
     pub state_data: Vec<u8>,
+    /// Hex encoded SHA-256 of the uncompressed state, as returned in [StateExportData::sha256].
+    /// The state is rejected if it does not match.
+    pub sha256: Option<String>,
 }
 
 impl Display for LoadStateRequest {
@@ -68,61 +193,171 @@ impl Display for LoadStateRequest {
     }
 }
 
-#[derive(CandidType)]
+#[derive(CandidType, Deserialize, Clone, Debug)]
 pub struct StateExportData {
     pub state_data: Vec<u8>,
+    /// Hex encoded SHA-256 of the uncompressed state.
+    pub sha256: String,
+    /// Hex encoded SHA-256 of `state_data` if it is encrypted, a checksum of the download that can
+    /// be verified without the secret key.
+    pub encrypted_sha256: Option<String>,
+    pub schema_version: u32,
+    /// Export time in nanoseconds.
+    pub exported_at: u64,
+    /// `state_data` is encrypted to the public key of the state exporter and must be decrypted
+    /// with `state_crypto::decrypt` before it can be loaded.
+    pub encrypted: bool,
 }
 
-#[derive(CandidType)]
-pub enum StateExportResponse {
-    Ok(StateExportData),
-    Err(ErrorInfo),
+actor_response!(pub StateExportResponse(StateExportData));
+
+/// A chunked state export or import, all chunks except the last one have `chunk_size` bytes.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct StateTransferSession {
+    pub session_id: u64,
+    pub total_size: u64,
+    pub chunk_size: u64,
+    pub chunk_count: u64,
+    /// Hex encoded SHA-256 of the whole state data.
+    pub sha256: String,
+    /// Hex encoded SHA-256 of the uncompressed state, see [StateExportData::sha256].
+    pub state_sha256: Option<String>,
+    /// Set for export sessions, see [StateExportData::schema_version].
+    pub schema_version: Option<u32>,
+    /// Set for export sessions, see [StateExportData::exported_at].
+    pub exported_at: Option<u64>,
+    /// Set for export sessions, see [StateExportData::encrypted].
+    pub encrypted: Option<bool>,
 }
 
-impl StateExportResponse {
-    pub fn new(result: ServiceResult<StateExportData>) -> StateExportResponse {
-        match result {
-            Ok(stats) => StateExportResponse::Ok(stats),
-            Err(err) => StateExportResponse::Err(err.into()),
-        }
+actor_response!(pub StateTransferSessionResponse(StateTransferSession));
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct StateChunk {
+    pub session_id: u64,
+    pub index: u64,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+    /// Hex encoded SHA-256 of `data`.
+    pub sha256: String,
+}
+
+impl Display for StateChunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "StateChunk {} of session {} with {} bytes",
+            self.index,
+            self.session_id,
+            self.data.len()
+        )
     }
 }
 
-pub fn encode_zlib(data: &[u8]) -> Vec<u8> {
-    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
-    encoder.write_all(data).unwrap();
-    let data = encoder.finish().unwrap();
-    data
+actor_response!(pub StateChunkResponse(StateChunk));
+
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BeginStateImportRequest {
+    pub total_size: u64,
+    pub chunk_count: u64,
+    /// Hex encoded SHA-256 of the whole state data.
+    pub sha256: String,
+    /// Hex encoded SHA-256 of the uncompressed state, checked like [LoadStateRequest::sha256].
+    pub state_sha256: Option<String>,
 }
 
-pub fn decode_zlib(data: &[u8]) -> Vec<u8> {
-    let mut d = ZlibDecoder::new(data);
-    let mut decoded_data = Vec::new();
-    d.read_to_end(&mut decoded_data).unwrap();
-    decoded_data
+/// Metadata of a state snapshot kept in stable memory.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct StateSnapshotInfo {
+    pub id: u64,
+    /// Time the snapshot was taken, in nanoseconds.
+    pub created_at: u64,
+    /// `GIT_COMMIT` of the wasm which took the snapshot.
+    pub git_commit: String,
+    /// Size of the encoded state in bytes.
+    pub size: u64,
 }
 
-pub fn to_state_export_data(source_state_data: Vec<u8>) -> StateExportData {
-    StateExportData {
-        state_data: encode_zlib(source_state_data.as_slice()),
-    }
+actor_response!(pub StateSnapshotResponse(StateSnapshotInfo));
+
+actor_response!(pub StateSnapshotListResponse(Vec<StateSnapshotInfo>));
+
+/// Hash of the current state, see `get_state_hash`.
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct StateHash {
+    /// Hex encoded SHA-256 of the encoded state, comparable with [StateExportData::sha256].
+    pub sha256: String,
+    pub schema_version: u32,
 }
 
-pub fn from_state_export_data(request: LoadStateRequest) -> Vec<u8> {
-    decode_zlib(request.state_data.as_slice())
+actor_response!(pub StateHashResponse(StateHash));
+
+pub fn to_state_export_data(
+    source_state_data: Vec<u8>,
+    schema_version: u32,
+    exported_at: TimeInNs,
+    compression: CompressionAlgorithm,
+) -> ServiceResult<StateExportData> {
+    Ok(StateExportData {
+        sha256: sha256_hex(source_state_data.as_slice()),
+        state_data: compress(source_state_data.as_slice(), compression)?,
+        schema_version,
+        exported_at: exported_at.0,
+        encrypted: false,
+        encrypted_sha256: None,
+    })
 }
 
-#[derive(CandidType)]
-pub enum GetStatsResponse<T> {
-    Ok(T),
-    Err(ErrorInfo),
+/// Parses the hex encoded X25519 public key of the state exporter.
+#[cfg(feature = "state_encryption")]
+pub fn parse_state_exporter_public_key(key: &str) -> ServiceResult<[u8; 32]> {
+    state_crypto::parse_key_hex(key).map_err(|err| CommonError::StateCryptoError {
+        detail: err.to_string(),
+    })
 }
 
-impl<T> GetStatsResponse<T> {
-    pub fn new(result: ServiceResult<T>) -> GetStatsResponse<T> {
-        match result {
-            Ok(stats) => GetStatsResponse::Ok(stats),
-            Err(err) => GetStatsResponse::Err(err.into()),
+/// Encrypts the compressed state data to `public_key`, see [state_crypto::encrypt].
+///
+/// `sha256` still covers the uncompressed state and is checked after decryption,
+/// `encrypted_sha256` is set to the hash of the encrypted data.
+#[cfg(feature = "state_encryption")]
+pub fn encrypt_state_export_data(
+    export_data: StateExportData,
+    public_key: &[u8; 32],
+    ephemeral_secret: [u8; 32],
+) -> ServiceResult<StateExportData> {
+    if export_data.encrypted {
+        return Ok(export_data);
+    }
+    let state_data = state_crypto::encrypt(
+        export_data.state_data.as_slice(),
+        public_key,
+        ephemeral_secret,
+    )
+    .map_err(|err| CommonError::StateCryptoError {
+        detail: err.to_string(),
+    })?;
+    Ok(StateExportData {
+        encrypted_sha256: Some(sha256_hex(state_data.as_slice())),
+        state_data,
+        encrypted: true,
+        ..export_data
+    })
+}
+
+pub fn from_state_export_data(request: LoadStateRequest) -> ServiceResult<Vec<u8>> {
+    #[cfg(feature = "state_encryption")]
+    if state_crypto::is_encrypted(request.state_data.as_slice()) {
+        return Err(CommonError::StateCryptoError {
+            detail: "state data is encrypted, decrypt it before loading".to_string(),
+        });
+    }
+    let state_data = decompress(request.state_data.as_slice(), STATE_MAX_DECOMPRESSED_SIZE)?;
+    if let Some(expected) = request.sha256 {
+        let actual = sha256_hex(state_data.as_slice());
+        if actual != expected {
+            return Err(StateTransferError::HashMismatch { expected, actual }.into());
         }
     }
+    Ok(state_data)
 }
@@ -0,0 +1,132 @@
+use std::io::{Read, Write};
+
+use candid::{CandidType, Deserialize};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use thiserror::Error;
+
+use crate::errors::ServiceResult;
+
+#[cfg(test)]
+mod tests;
+
+const CODEC_TAG_ZLIB: u8 = 0x01;
+const CODEC_TAG_LZ4: u8 = 0x02;
+/// First byte of a zlib stream with the default window size, exports written before the codec
+/// header was introduced are plain zlib streams and start with it.
+const LEGACY_ZLIB_FIRST_BYTE: u8 = 0x78;
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+pub enum CodecError {
+    #[error("compressed data is empty")]
+    EmptyData,
+    #[error("unknown compression algorithm tag {tag:?}")]
+    UnknownAlgorithm { tag: u8 },
+    #[error("decompressed data exceeds the max size of {max_size:?} bytes")]
+    TooLarge { max_size: u64 },
+    #[error("failed to compress data, detail: {detail:?}")]
+    CompressFailed { detail: String },
+    #[error("failed to decompress data, detail: {detail:?}")]
+    DecompressFailed { detail: String },
+}
+
+/// Compression algorithm of encoded data, written as the first byte by [compress].
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CompressionAlgorithm {
+    /// Better ratio, compatible with exports written before the codec header.
+    Zlib,
+    /// Much faster, with a lower ratio.
+    Lz4,
+}
+
+impl Default for CompressionAlgorithm {
+    fn default() -> Self {
+        CompressionAlgorithm::Zlib
+    }
+}
+
+impl CompressionAlgorithm {
+    pub fn tag(&self) -> u8 {
+        match self {
+            CompressionAlgorithm::Zlib => CODEC_TAG_ZLIB,
+            CompressionAlgorithm::Lz4 => CODEC_TAG_LZ4,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            CODEC_TAG_ZLIB => Some(CompressionAlgorithm::Zlib),
+            CODEC_TAG_LZ4 => Some(CompressionAlgorithm::Lz4),
+            _ => None,
+        }
+    }
+}
+
+/// Reads `reader` to the end, failing as soon as more than `max_size` bytes are produced.
+fn read_bounded<R: Read>(reader: R, max_size: u64) -> ServiceResult<Vec<u8>> {
+    let mut decoded_data = Vec::new();
+    reader
+        .take(max_size + 1)
+        .read_to_end(&mut decoded_data)
+        .map_err(|err| CodecError::DecompressFailed {
+            detail: err.to_string(),
+        })?;
+    if decoded_data.len() as u64 > max_size {
+        return Err(CodecError::TooLarge { max_size }.into());
+    }
+    Ok(decoded_data)
+}
+
+fn compress_failed(err: std::io::Error) -> CodecError {
+    CodecError::CompressFailed {
+        detail: err.to_string(),
+    }
+}
+
+pub fn encode_zlib(data: &[u8]) -> ServiceResult<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).map_err(compress_failed)?;
+    Ok(encoder.finish().map_err(compress_failed)?)
+}
+
+pub fn decode_zlib(data: &[u8], max_size: u64) -> ServiceResult<Vec<u8>> {
+    read_bounded(ZlibDecoder::new(data), max_size)
+}
+
+pub fn encode_lz4(data: &[u8]) -> ServiceResult<Vec<u8>> {
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+    encoder.write_all(data).map_err(compress_failed)?;
+    Ok(encoder.finish().map_err(|err| CodecError::CompressFailed {
+        detail: err.to_string(),
+    })?)
+}
+
+pub fn decode_lz4(data: &[u8], max_size: u64) -> ServiceResult<Vec<u8>> {
+    read_bounded(lz4_flex::frame::FrameDecoder::new(data), max_size)
+}
+
+/// Compresses `data` with `algorithm`, prefixed with the tag of the algorithm.
+pub fn compress(data: &[u8], algorithm: CompressionAlgorithm) -> ServiceResult<Vec<u8>> {
+    let body = match algorithm {
+        CompressionAlgorithm::Zlib => encode_zlib(data)?,
+        CompressionAlgorithm::Lz4 => encode_lz4(data)?,
+    };
+    let mut encoded = Vec::with_capacity(body.len() + 1);
+    encoded.push(algorithm.tag());
+    encoded.extend_from_slice(&body);
+    Ok(encoded)
+}
+
+/// Decompresses data written by [compress], or a plain zlib stream without a tag.
+pub fn decompress(data: &[u8], max_size: u64) -> ServiceResult<Vec<u8>> {
+    let (tag, body) = data.split_first().ok_or(CodecError::EmptyData)?;
+    if *tag == LEGACY_ZLIB_FIRST_BYTE {
+        return decode_zlib(data, max_size);
+    }
+    match CompressionAlgorithm::from_tag(*tag) {
+        Some(CompressionAlgorithm::Zlib) => decode_zlib(body, max_size),
+        Some(CompressionAlgorithm::Lz4) => decode_lz4(body, max_size),
+        None => Err(CodecError::UnknownAlgorithm { tag: *tag }.into()),
+    }
+}
@@ -0,0 +1,70 @@
+use rstest::*;
+
+use super::*;
+use crate::errors::CommonError;
+use crate::test_common::test::init_test;
+
+const MAX_SIZE: u64 = 1024;
+
+#[fixture]
+fn data() -> Vec<u8> {
+    init_test();
+    (0..MAX_SIZE).map(|i| (i % 7) as u8).collect()
+}
+
+#[rstest]
+#[case(CompressionAlgorithm::Zlib)]
+#[case(CompressionAlgorithm::Lz4)]
+fn test_compress_and_decompress(data: Vec<u8>, #[case] algorithm: CompressionAlgorithm) {
+    let compressed = compress(&data, algorithm).unwrap();
+    assert_eq!(compressed[0], algorithm.tag());
+    assert!(compressed.len() < data.len());
+    assert_eq!(decompress(&compressed, MAX_SIZE), Ok(data));
+}
+
+#[rstest]
+fn test_decompress_legacy_zlib(data: Vec<u8>) {
+    let compressed = encode_zlib(&data).unwrap();
+    assert_eq!(compressed[0], LEGACY_ZLIB_FIRST_BYTE);
+    assert_eq!(decompress(&compressed, MAX_SIZE), Ok(data));
+}
+
+#[rstest]
+#[case(CompressionAlgorithm::Zlib)]
+#[case(CompressionAlgorithm::Lz4)]
+fn test_decompress_rejects_too_large(data: Vec<u8>, #[case] algorithm: CompressionAlgorithm) {
+    let compressed = compress(&data, algorithm).unwrap();
+    assert_eq!(
+        decompress(&compressed, MAX_SIZE - 1),
+        Err(CommonError::CodecError(CodecError::TooLarge {
+            max_size: MAX_SIZE - 1
+        }))
+    );
+}
+
+#[rstest]
+fn test_decompress_rejects_unknown_algorithm() {
+    init_test();
+    assert_eq!(
+        decompress(&[0x03, 1, 2, 3], MAX_SIZE),
+        Err(CommonError::CodecError(CodecError::UnknownAlgorithm {
+            tag: 0x03
+        }))
+    );
+    assert_eq!(
+        decompress(&[], MAX_SIZE),
+        Err(CommonError::CodecError(CodecError::EmptyData))
+    );
+}
+
+#[rstest]
+#[case(CompressionAlgorithm::Zlib)]
+#[case(CompressionAlgorithm::Lz4)]
+fn test_decompress_rejects_malformed_data(#[case] algorithm: CompressionAlgorithm) {
+    init_test();
+    let result = decompress(&[algorithm.tag(), 1, 2, 3], MAX_SIZE);
+    assert!(matches!(
+        result,
+        Err(CommonError::CodecError(CodecError::DecompressFailed { .. }))
+    ));
+}
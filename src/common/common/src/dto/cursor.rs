@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use candid::{decode_one, encode_one, CandidType, Deserialize};
+use serde::de::DeserializeOwned;
+
+use crate::constants::{PAGE_INPUT_MAX_LIMIT, PAGE_INPUT_MIN_LIMIT};
+use crate::errors::{CommonError, ServiceResult};
+
+#[cfg(test)]
+mod tests;
+
+/// Input of keyset paging. `cursor` is the `next_cursor` of the previous page, `None` for the first page.
+///
+/// Unlike [crate::dto::GetPageInput] the position is the last returned key rather than an offset,
+/// so pages do not shift when items are inserted or removed before the cursor.
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct GetCursorPageInput {
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+impl GetCursorPageInput {
+    pub fn validate(&self) -> ServiceResult<()> {
+        let max_limit = PAGE_INPUT_MAX_LIMIT;
+        let min_limit = PAGE_INPUT_MIN_LIMIT;
+        if self.limit > max_limit || self.limit < min_limit {
+            return Err(CommonError::ValueShouldBeInRangeError {
+                field: "limit".to_string(),
+                min: min_limit,
+                max: max_limit,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the key encoded in `cursor`, `None` for the first page.
+    pub fn cursor_key<K: CandidType + DeserializeOwned>(&self) -> ServiceResult<Option<K>> {
+        self.cursor.as_deref().map(decode_cursor).transpose()
+    }
+}
+
+#[derive(CandidType, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct GetCursorPageOutput<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, `None` when this is the last page.
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl<T> GetCursorPageOutput<T> {
+    pub fn new(items: Vec<T>, next_cursor: Option<String>) -> Self {
+        Self {
+            has_more: next_cursor.is_some(),
+            items,
+            next_cursor,
+        }
+    }
+}
+
+/// Encodes `key` into an opaque cursor, the hex of its candid encoding.
+pub fn encode_cursor<K: CandidType>(key: &K) -> String {
+    hex::encode(encode_one(key).expect("failed to encode page cursor"))
+}
+
+pub fn decode_cursor<K: CandidType + DeserializeOwned>(cursor: &str) -> ServiceResult<K> {
+    let bytes = hex::decode(cursor).map_err(|err| CommonError::InvalidPageCursor {
+        detail: err.to_string(),
+    })?;
+    decode_one(&bytes).map_err(|err| CommonError::InvalidPageCursor {
+        detail: err.to_string(),
+    })
+}
+
+/// Pages items sorted by ascending, unique keys, starting after the key of `input.cursor`.
+///
+/// `key_of` must return the key the items are sorted by, the key of the last item is the next cursor.
+pub fn page_sorted<I, K, T, F>(
+    items: I,
+    input: &GetCursorPageInput,
+    key_of: F,
+) -> ServiceResult<GetCursorPageOutput<T>>
+where
+    I: IntoIterator<Item = T>,
+    K: CandidType + DeserializeOwned + Ord,
+    F: Fn(&T) -> K,
+{
+    input.validate()?;
+    let cursor_key: Option<K> = input.cursor_key()?;
+    let mut items = items.into_iter().skip_while(|item| {
+        cursor_key
+            .as_ref()
+            .map_or(false, |cursor| key_of(item) <= *cursor)
+    });
+    let page: Vec<T> = items.by_ref().take(input.limit).collect();
+    let next_cursor = match (page.last(), items.next()) {
+        (Some(last), Some(_)) => Some(encode_cursor(&key_of(last))),
+        _ => None,
+    };
+    Ok(GetCursorPageOutput::new(page, next_cursor))
+}
+
+/// Pages the entries of `map` in key order, `map_item` converts an entry to a page item.
+pub fn page_btree_map<K, V, T, F>(
+    map: &BTreeMap<K, V>,
+    input: &GetCursorPageInput,
+    map_item: F,
+) -> ServiceResult<GetCursorPageOutput<T>>
+where
+    K: CandidType + DeserializeOwned + Ord,
+    F: Fn(&K, &V) -> T,
+{
+    input.validate()?;
+    let lower = match input.cursor_key::<K>()? {
+        Some(key) => Bound::Excluded(key),
+        None => Bound::Unbounded,
+    };
+    let mut entries = map.range((lower, Bound::Unbounded));
+    let page: Vec<(&K, &V)> = entries.by_ref().take(input.limit).collect();
+    let next_cursor = match (page.last(), entries.next()) {
+        (Some((key, _)), Some(_)) => Some(encode_cursor(*key)),
+        _ => None,
+    };
+    Ok(GetCursorPageOutput::new(
+        page.into_iter()
+            .map(|(key, value)| map_item(key, value))
+            .collect(),
+        next_cursor,
+    ))
+}
@@ -0,0 +1,91 @@
+use rstest::*;
+
+use super::*;
+use crate::test_common::test::init_test;
+
+#[fixture]
+fn names() -> BTreeMap<String, u64> {
+    init_test();
+    (1..=5).map(|i| (format!("name{}", i), i)).collect()
+}
+
+fn input(cursor: Option<String>, limit: usize) -> GetCursorPageInput {
+    GetCursorPageInput { cursor, limit }
+}
+
+#[rstest]
+fn test_page_btree_map(names: BTreeMap<String, u64>) {
+    let first = page_btree_map(&names, &input(None, 2), |_, value| *value).unwrap();
+    assert_eq!(first.items, vec![1, 2]);
+    assert!(first.has_more);
+    assert_eq!(first.next_cursor, Some(encode_cursor(&"name2".to_string())));
+
+    let second = page_btree_map(&names, &input(first.next_cursor, 2), |_, value| *value).unwrap();
+    assert_eq!(second.items, vec![3, 4]);
+
+    let last = page_btree_map(&names, &input(second.next_cursor, 2), |_, value| *value).unwrap();
+    assert_eq!(last, GetCursorPageOutput::new(vec![5], None));
+    assert!(!last.has_more);
+}
+
+#[rstest]
+fn test_page_is_stable_when_items_are_inserted_before_cursor(mut names: BTreeMap<String, u64>) {
+    let first = page_btree_map(&names, &input(None, 2), |_, value| *value).unwrap();
+    names.insert("name0".to_string(), 0);
+
+    let second = page_btree_map(&names, &input(first.next_cursor, 2), |_, value| *value).unwrap();
+    assert_eq!(second.items, vec![3, 4]);
+}
+
+#[rstest]
+fn test_exact_last_page_has_no_next_cursor(names: BTreeMap<String, u64>) {
+    let page = page_btree_map(&names, &input(None, 5), |_, value| *value).unwrap();
+    assert_eq!(page.items.len(), 5);
+    assert_eq!(page.next_cursor, None);
+}
+
+#[rstest]
+fn test_page_sorted(names: BTreeMap<String, u64>) {
+    let items: Vec<(String, u64)> = names.into_iter().collect();
+    let first = page_sorted(items.clone(), &input(None, 3), |(_, value)| *value).unwrap();
+    assert_eq!(first.items.len(), 3);
+    assert_eq!(first.next_cursor, Some(encode_cursor(&3u64)));
+
+    let last = page_sorted(items, &input(first.next_cursor, 3), |(_, value)| *value).unwrap();
+    assert_eq!(
+        last,
+        GetCursorPageOutput::new(
+            vec![("name4".to_string(), 4), ("name5".to_string(), 5)],
+            None
+        )
+    );
+}
+
+#[rstest]
+fn test_invalid_cursor(names: BTreeMap<String, u64>) {
+    let result = page_btree_map(&names, &input(Some("zz".to_string()), 2), |_, value| *value);
+    assert!(matches!(result, Err(CommonError::InvalidPageCursor { .. })));
+
+    // a cursor of another key type
+    let result = page_btree_map(&names, &input(Some(encode_cursor(&1u64)), 2), |_, value| {
+        *value
+    });
+    assert!(matches!(result, Err(CommonError::InvalidPageCursor { .. })));
+}
+
+#[rstest]
+fn test_limit_out_of_range(names: BTreeMap<String, u64>) {
+    let result = page_btree_map(
+        &names,
+        &input(None, PAGE_INPUT_MAX_LIMIT + 1),
+        |_, value| *value,
+    );
+    assert_eq!(
+        result,
+        Err(CommonError::ValueShouldBeInRangeError {
+            field: "limit".to_string(),
+            min: PAGE_INPUT_MIN_LIMIT,
+            max: PAGE_INPUT_MAX_LIMIT,
+        })
+    );
+}
@@ -1,6 +1,6 @@
 use rstest::*;
 
-use crate::constants::{PAGE_INPUT_MAX_LIMIT, PAGE_INPUT_MAX_OFFSET};
+use crate::constants::{PAGE_INPUT_MAX_FILTERS, PAGE_INPUT_MAX_LIMIT, PAGE_INPUT_MAX_OFFSET};
 use crate::dto::*;
 use crate::errors::CommonError;
 use crate::test_common::test::init_test;
@@ -13,23 +13,22 @@ pub fn setup() {
 mod get_page_input {
     use super::*;
 
+    const FIELDS: PageFields = PageFields {
+        sortable: &["name", "created_at"],
+        filterable: &["owner"],
+    };
+
     #[rstest]
     fn test_get_page_input(_setup: ()) {
-        let input = GetPageInput {
-            limit: 10,
-            offset: 0,
-        };
-        assert_eq!(input.validate(), Ok(()));
+        let input = GetPageInput::new(0, 10);
+        assert_eq!(input.validate(&PageFields::NONE), Ok(()));
     }
 
     #[rstest]
     fn test_get_page_input_limit_overflow(_setup: ()) {
-        let input = GetPageInput {
-            limit: PAGE_INPUT_MAX_LIMIT + 1,
-            offset: 0,
-        };
+        let input = GetPageInput::new(0, PAGE_INPUT_MAX_LIMIT + 1);
         assert_eq!(
-            input.validate(),
+            input.validate(&PageFields::NONE),
             Err(CommonError::ValueShouldBeInRangeError {
                 field: "limit".to_string(),
                 min: 1,
@@ -40,12 +39,9 @@ mod get_page_input {
 
     #[rstest]
     fn test_get_page_input_offset_overflow(_setup: ()) {
-        let input = GetPageInput {
-            limit: 100,
-            offset: PAGE_INPUT_MAX_OFFSET + 1,
-        };
+        let input = GetPageInput::new(PAGE_INPUT_MAX_OFFSET + 1, 100);
         assert_eq!(
-            input.validate(),
+            input.validate(&PageFields::NONE),
             Err(CommonError::ValueShouldBeInRangeError {
                 field: "offset".to_string(),
                 min: 0,
@@ -53,4 +49,217 @@ mod get_page_input {
             })
         );
     }
+
+    #[rstest]
+    fn test_get_page_input_sort_and_filter(_setup: ()) {
+        let input = GetPageInput {
+            sort: Some(SortSpec {
+                field: "created_at".to_string(),
+                direction: SortDirection::Desc,
+            }),
+            filters: Some(vec![FilterSpec {
+                field: "owner".to_string(),
+                operator: FilterOperator::Eq,
+                value: "2vxsx-fae".to_string(),
+            }]),
+            ..GetPageInput::new(0, 10)
+        };
+        assert_eq!(input.validate(&FIELDS), Ok(()));
+        assert_eq!(
+            input.validate(&PageFields::NONE),
+            Err(CommonError::PageFieldNotAllowed {
+                field: "created_at".to_string(),
+                usage: "sort".to_string(),
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_get_page_input_filter_not_allowed(_setup: ()) {
+        let input = GetPageInput {
+            filters: Some(vec![FilterSpec {
+                field: "name".to_string(),
+                operator: FilterOperator::StartsWith,
+                value: "a".to_string(),
+            }]),
+            ..GetPageInput::new(0, 10)
+        };
+        assert_eq!(
+            input.validate(&FIELDS),
+            Err(CommonError::PageFieldNotAllowed {
+                field: "name".to_string(),
+                usage: "filter".to_string(),
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_get_page_input_too_many_filters(_setup: ()) {
+        let filter = FilterSpec {
+            field: "owner".to_string(),
+            operator: FilterOperator::Ne,
+            value: "2vxsx-fae".to_string(),
+        };
+        let input = GetPageInput {
+            filters: Some(vec![filter; PAGE_INPUT_MAX_FILTERS + 1]),
+            ..GetPageInput::new(0, 10)
+        };
+        assert_eq!(
+            input.validate(&FIELDS),
+            Err(CommonError::ValueShouldBeInRangeError {
+                field: "filters".to_string(),
+                min: 0,
+                max: PAGE_INPUT_MAX_FILTERS,
+            })
+        );
+    }
+}
+
+mod get_page_output {
+    use super::*;
+
+    #[rstest]
+    #[case(0, vec![0, 1], true)]
+    #[case(4, vec![4], false)]
+    #[case(6, vec![], false)]
+    fn test_from_items(
+        _setup: (),
+        #[case] offset: usize,
+        #[case] items: Vec<u32>,
+        #[case] has_more: bool,
+    ) {
+        let input = GetPageInput::new(offset, 2);
+        let output = GetPageOutput::from_items((0..5).collect(), input.clone());
+        assert_eq!(
+            output,
+            GetPageOutput {
+                items,
+                total: 5,
+                has_more,
+                input,
+            }
+        );
+    }
+}
+
+mod state_export_data {
+    use super::*;
+    use crate::state::transfer::{sha256_hex, StateTransferError};
+    use crate::types::TimeInNs;
+
+    #[rstest]
+    fn test_export_and_load(_setup: ()) {
+        let export_data =
+            to_state_export_data(vec![1, 2, 3], 2, TimeInNs(100), CompressionAlgorithm::Lz4)
+                .unwrap();
+        assert_eq!(export_data.sha256, sha256_hex(&[1, 2, 3]));
+        assert_eq!(export_data.schema_version, 2);
+        assert_eq!(export_data.exported_at, 100);
+
+        let loaded = from_state_export_data(LoadStateRequest {
+            state_data: export_data.state_data,
+            sha256: Some(export_data.sha256),
+        });
+        assert_eq!(loaded, Ok(vec![1, 2, 3]));
+    }
+
+    #[rstest]
+    fn test_load_without_hash(_setup: ()) {
+        let export_data =
+            to_state_export_data(vec![1, 2, 3], 1, TimeInNs(100), CompressionAlgorithm::Zlib)
+                .unwrap();
+        let loaded = from_state_export_data(LoadStateRequest {
+            state_data: export_data.state_data,
+            sha256: None,
+        });
+        assert_eq!(loaded, Ok(vec![1, 2, 3]));
+    }
+
+    #[rstest]
+    fn test_load_rejects_hash_mismatch(_setup: ()) {
+        let export_data =
+            to_state_export_data(vec![1, 2, 3], 1, TimeInNs(100), CompressionAlgorithm::Zlib)
+                .unwrap();
+        let loaded = from_state_export_data(LoadStateRequest {
+            state_data: export_data.state_data,
+            sha256: Some(sha256_hex(&[3, 2, 1])),
+        });
+        assert_eq!(
+            loaded,
+            Err(CommonError::StateTransferError(
+                StateTransferError::HashMismatch {
+                    expected: sha256_hex(&[3, 2, 1]),
+                    actual: sha256_hex(&[1, 2, 3]),
+                }
+            ))
+        );
+    }
+}
+
+#[cfg(feature = "state_encryption")]
+mod encrypted_state_export_data {
+    use super::*;
+    use crate::state::transfer::sha256_hex;
+    use crate::types::TimeInNs;
+
+    const EXPORTER_SECRET: [u8; 32] = [7u8; 32];
+
+    #[rstest]
+    fn test_encrypt_and_load_after_decrypt(_setup: ()) {
+        let public_key = state_crypto::public_key_from_secret(EXPORTER_SECRET);
+        let export_data =
+            to_state_export_data(vec![1, 2, 3], 1, TimeInNs(100), CompressionAlgorithm::Zlib)
+                .unwrap();
+        let compressed = export_data.state_data.clone();
+
+        let state_sha256 = export_data.sha256.clone();
+
+        let encrypted = encrypt_state_export_data(export_data, &public_key, [9u8; 32]).unwrap();
+        assert!(encrypted.encrypted);
+        assert_ne!(encrypted.state_data, compressed);
+        assert_eq!(encrypted.sha256, state_sha256);
+        assert_eq!(
+            encrypted.encrypted_sha256,
+            Some(sha256_hex(&encrypted.state_data))
+        );
+
+        let loaded = from_state_export_data(LoadStateRequest {
+            state_data: encrypted.state_data.clone(),
+            sha256: Some(encrypted.sha256.clone()),
+        });
+        assert_eq!(
+            loaded,
+            Err(CommonError::StateCryptoError {
+                detail: "state data is encrypted, decrypt it before loading".to_string(),
+            })
+        );
+
+        let decrypted = state_crypto::decrypt(&encrypted.state_data, &EXPORTER_SECRET).unwrap();
+        assert_eq!(decrypted, compressed);
+        let loaded = from_state_export_data(LoadStateRequest {
+            state_data: decrypted,
+            sha256: Some(state_sha256),
+        });
+        assert_eq!(loaded, Ok(vec![1, 2, 3]));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("zz")]
+    #[case("0102")]
+    fn test_parse_invalid_public_key(_setup: (), #[case] key: &str) {
+        assert!(matches!(
+            parse_state_exporter_public_key(key),
+            Err(CommonError::StateCryptoError { .. })
+        ));
+    }
+
+    #[rstest]
+    fn test_parse_public_key(_setup: ()) {
+        let public_key = state_crypto::public_key_from_secret(EXPORTER_SECRET);
+        assert_eq!(
+            parse_state_exporter_public_key(&hex::encode(public_key)),
+            Ok(public_key)
+        );
+    }
 }
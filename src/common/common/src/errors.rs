@@ -1,51 +1,288 @@
+use crate::canister_api::reject::{CallFailureKind, RejectCode};
+use crate::dto::codec::CodecError;
+use crate::state::collections::StableCollectionError;
+use crate::state::snapshots::StateSnapshotError;
+use crate::state::transfer::StateTransferError;
+use crate::state::StateDecodeError;
 use candid::{CandidType, Deserialize};
+use log::debug;
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use thiserror::Error;
 
-#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+pub use common_macros::ErrorCode;
+
+pub mod i18n;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(
+    Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error, ErrorCode,
+)]
+#[error_code(namespace = 0, name = "common")]
 pub enum CommonError {
     #[error("error from remote, {0:?}")]
+    #[error_code(code = 2, http_status = 502)]
     RemoteError(ErrorInfo),
     #[error("Unauthorized, please login first")]
+    #[error_code(code = 3, http_status = 401)]
     Unauthorized,
     #[error("Permission denied")]
+    #[error_code(code = 4, http_status = 403)]
     PermissionDenied,
     #[error("Length of {field:?} must be in range [{min:?}, {max:?})")]
+    #[error_code(code = 5, http_status = 400)]
     ValueShouldBeInRangeError {
         field: String,
         min: usize,
         max: usize,
     },
     #[error("canister call error, rejected by {rejection_code:?}")]
+    #[error_code(code = 6, http_status = 502)]
     CanisterCallError {
         message: String,
-        rejection_code: String,
+        rejection_code: RejectCode,
     },
+    #[error("failed to decode state, {0}")]
+    #[error_code(code = 7, inner_http_status)]
+    StateDecodeError(StateDecodeError),
+    #[error("state transfer failed, {0}")]
+    #[error_code(code = 8, inner_http_status)]
+    StateTransferError(StateTransferError),
+    #[error("state snapshot error, {0}")]
+    #[error_code(code = 9, inner_http_status)]
+    StateSnapshotError(StateSnapshotError),
+    #[error("codec error, {0}")]
+    #[error_code(code = 10, http_status = 400)]
+    CodecError(CodecError),
+    #[error("state encryption error, detail: {detail:?}")]
+    #[error_code(code = 11, http_status = 400)]
+    StateCryptoError { detail: String },
+    #[error("invalid page cursor, detail: {detail:?}")]
+    #[error_code(code = 12, http_status = 400)]
+    InvalidPageCursor { detail: String },
+    #[error("{usage} by {field:?} is not allowed")]
+    #[error_code(code = 13, http_status = 400)]
+    PageFieldNotAllowed { field: String, usage: String },
+    #[error("no route for {path:?}")]
+    #[error_code(code = 14, http_status = 404)]
+    HttpRouteNotFound { path: String },
+    #[error("method {method} is not allowed for {path:?}")]
+    #[error_code(code = 15, http_status = 405)]
+    HttpMethodNotAllowed { method: String, path: String },
+    #[error("invalid streaming token, detail: {detail:?}")]
+    #[error_code(code = 16, http_status = 400)]
+    InvalidStreamingToken { detail: String },
+    #[error("invalid url {url:?}, detail: {detail:?}")]
+    #[error_code(code = 17, http_status = 400)]
+    InvalidUrl { url: String, detail: String },
+    #[error("stable collection error, {0}")]
+    #[error_code(code = 18, inner_http_status)]
+    StableCollectionError(StableCollectionError),
     #[error("Unknown error, detail: {detail:?}")]
+    #[error_code(code = 10000)]
     Unknown { detail: String },
 }
 
 impl CommonError {
-    pub(crate) fn code(&self) -> u32 {
+    /// Returns how the canister call failed, `None` if the error is not a failed call.
+    pub fn call_failure_kind(&self) -> Option<CallFailureKind> {
         match self {
-            CommonError::RemoteError(_) => 2,
-            CommonError::Unauthorized => 3,
-            CommonError::PermissionDenied => 4,
-            CommonError::ValueShouldBeInRangeError { .. } => 5,
-            CommonError::CanisterCallError { .. } => 6,
-            CommonError::Unknown { .. } => 10000,
+            CommonError::CanisterCallError { rejection_code, .. } => {
+                Some(rejection_code.failure_kind())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the failed call was not executed and may succeed later, so it can be retried
+    /// without executing it twice, e.g. a transfer.
+    pub fn is_retryable(&self) -> bool {
+        self.call_failure_kind() == Some(CallFailureKind::Transient)
+    }
+
+    /// Whether the failed call may have been executed, so the callee state must be checked before
+    /// retrying it.
+    pub fn is_outcome_unknown(&self) -> bool {
+        self.call_failure_kind() == Some(CallFailureKind::UnknownOutcome)
+    }
+}
+
+/// Number of codes in one error namespace.
+pub const ERROR_NAMESPACE_SIZE: u32 = 100_000;
+
+/// An error enum with stable codes, derived with `#[derive(ErrorCode)]`.
+///
+/// The full code of an error is `NAMESPACE * ERROR_NAMESPACE_SIZE + local_code`. Namespaces in use:
+/// - 0: [CommonError], so its codes are the same as before namespaces were introduced
+/// - 1: errors of the api mock canister
+pub trait ErrorCode {
+    const NAMESPACE: u32;
+    const NAMESPACE_NAME: &'static str;
+
+    /// Code of the error within its namespace.
+    fn local_code(&self) -> u32;
+
+    fn code(&self) -> u32 {
+        Self::NAMESPACE * ERROR_NAMESPACE_SIZE + self.local_code()
+    }
+
+    /// Status of http error responses, see [crate::http::HttpResponse::from_error].
+    fn http_status(&self) -> u16 {
+        500
+    }
+
+    /// Fields of the error, returned to clients as [ErrorInfo::details].
+    fn details(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+
+    /// All codes of the enum, in declaration order.
+    fn catalog() -> Vec<ErrorCodeEntry>;
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize)]
+pub struct ErrorCodeEntry {
+    pub code: u32,
+    pub namespace: String,
+    /// Name of the enum variant.
+    pub name: String,
+    /// Message template, placeholders such as `{field:?}` are filled in [ErrorInfo::message].
+    pub message: String,
+}
+
+impl ErrorCodeEntry {
+    pub fn new<E: ErrorCode>(local_code: u32, name: &str, message: &str) -> Self {
+        Self {
+            code: E::NAMESPACE * ERROR_NAMESPACE_SIZE + local_code,
+            namespace: E::NAMESPACE_NAME.to_string(),
+            name: name.to_string(),
+            message: message.to_string(),
         }
     }
 }
 
+/// Merges the catalogs of several error enums, sorted by code.
+///
+/// Panics if two entries share a code, e.g. because two enums are declared in the same namespace.
+pub fn merge_error_catalogs(catalogs: Vec<Vec<ErrorCodeEntry>>) -> Vec<ErrorCodeEntry> {
+    let mut entries: Vec<ErrorCodeEntry> = catalogs.into_iter().flatten().collect();
+    entries.sort();
+    for pair in entries.windows(2) {
+        assert_ne!(
+            pair[0].code, pair[1].code,
+            "error code {} is used by both {}::{} and {}::{}",
+            pair[0].code, pair[0].namespace, pair[0].name, pair[1].namespace, pair[1].name
+        );
+    }
+    entries
+}
+
 /// Error information
+///
+/// `details` and `trace_id` are `opt` fields, so clients decoding only `code` and `message` keep
+/// working.
 #[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize)]
 pub struct ErrorInfo {
     /// Error code
     pub code: u32,
     /// Error message
     pub message: String,
+    /// Fields of the error variant, e.g. `field`, `min` and `max` of a range error
+    pub details: Option<BTreeMap<String, String>>,
+    /// Id to find the error in the canister logs
+    pub trace_id: Option<String>,
+}
+
+impl ErrorInfo {
+    pub fn new(code: u32, message: String) -> Self {
+        Self {
+            code,
+            message,
+            details: None,
+            trace_id: None,
+        }
+    }
+
+    /// Converts `error` with its fields as details, the error is logged with a new trace id.
+    pub fn from_error<E: ErrorCode + Display>(error: &E) -> Self {
+        let trace_id = next_trace_id();
+        let message = error.to_string();
+        debug!("error {} returned, trace id: {}", message, trace_id);
+        let details = error.details();
+        Self {
+            code: error.code(),
+            message,
+            details: if details.is_empty() {
+                None
+            } else {
+                Some(details)
+            },
+            trace_id: Some(trace_id),
+        }
+    }
+
+    /// Replaces the trace id, e.g. with a correlation id received from the client.
+    pub fn with_trace_id(mut self, trace_id: String) -> Self {
+        self.trace_id = Some(trace_id);
+        self
+    }
+
+    /// Returns the reject code of a [CommonError::CanisterCallError], `None` for other errors.
+    ///
+    /// The canister apis return failed calls as [ErrorInfo], the code is read back from the
+    /// `rejection_code` detail. An error returned by the callee for one of its own failed calls
+    /// has the same code, so only use this on errors of calls made by this canister.
+    pub fn rejection_code(&self) -> Option<RejectCode> {
+        let call_error_code = CommonError::CanisterCallError {
+            message: String::new(),
+            rejection_code: RejectCode::Unknown,
+        }
+        .code();
+        if self.code != call_error_code {
+            return None;
+        }
+        self.details
+            .as_ref()
+            .and_then(|details| details.get("rejection_code"))
+            .and_then(|code| code.parse().ok())
+    }
+
+    /// See [CommonError::call_failure_kind].
+    pub fn call_failure_kind(&self) -> Option<CallFailureKind> {
+        self.rejection_code().map(|code| code.failure_kind())
+    }
+
+    /// See [CommonError::is_retryable].
+    pub fn is_retryable(&self) -> bool {
+        self.call_failure_kind() == Some(CallFailureKind::Transient)
+    }
+
+    /// See [CommonError::is_outcome_unknown].
+    pub fn is_outcome_unknown(&self) -> bool {
+        self.call_failure_kind() == Some(CallFailureKind::UnknownOutcome)
+    }
+}
+
+thread_local! {
+    static TRACE_SEQUENCE: Cell<u64> = Cell::new(0);
+}
+
+/// Returns a new trace id, `<time in ns>-<sequence>` in hex. The time is 0 outside of a canister.
+pub fn next_trace_id() -> String {
+    #[cfg(target_arch = "wasm32")]
+    let now = ic_cdk::api::time();
+    #[cfg(not(target_arch = "wasm32"))]
+    let now = 0u64;
+    let sequence = TRACE_SEQUENCE.with(|sequence| {
+        let next = sequence.get().wrapping_add(1);
+        sequence.set(next);
+        next
+    });
+    format!("{:x}-{:x}", now, sequence)
 }
 
 impl Display for ErrorInfo {
@@ -55,10 +292,7 @@ impl Display for ErrorInfo {
 }
 
 pub fn get_error_code(error: CommonError) -> ErrorInfo {
-    ErrorInfo {
-        code: error.code(),
-        message: error.to_string(),
-    }
+    ErrorInfo::from_error(&error)
 }
 
 pub type ServiceResult<T> = anyhow::Result<T, CommonError>;
@@ -71,25 +305,219 @@ impl From<CommonError> for ErrorInfo {
     }
 }
 
+/// Status of http error responses of an error wrapped by an [ErrorCode] variant marked with
+/// `#[error_code(inner_http_status)]`.
+pub trait HttpStatus {
+    fn http_status(&self) -> u16;
+}
+
+impl HttpStatus for StateDecodeError {
+    fn http_status(&self) -> u16 {
+        match self {
+            StateDecodeError::MigrationFailed { .. } => 500,
+            _ => 400,
+        }
+    }
+}
+
+impl HttpStatus for StateTransferError {
+    fn http_status(&self) -> u16 {
+        match self {
+            StateTransferError::SessionNotFound { .. } => 404,
+            StateTransferError::TooManySessions { .. }
+            | StateTransferError::ImportCapacityExceeded { .. } => 429,
+            StateTransferError::StateTooLarge { .. } => 413,
+            StateTransferError::ChunkOutOfRange { .. }
+            | StateTransferError::ChunkHashMismatch { .. }
+            | StateTransferError::ChunkMissing { .. }
+            | StateTransferError::InvalidChunkCount { .. }
+            | StateTransferError::SizeMismatch { .. }
+            | StateTransferError::HashMismatch { .. }
+            | StateTransferError::ChunkSizeMismatch { .. } => 400,
+        }
+    }
+}
+
+impl HttpStatus for StateSnapshotError {
+    fn http_status(&self) -> u16 {
+        match self {
+            StateSnapshotError::NotFound { .. } => 404,
+            StateSnapshotError::StorageError { .. } => 500,
+        }
+    }
+}
+
+impl HttpStatus for StableCollectionError {
+    fn http_status(&self) -> u16 {
+        match self {
+            StableCollectionError::ValueTooLarge { .. } => 413,
+            StableCollectionError::GrowFailed { .. } => 500,
+        }
+    }
+}
+
+impl From<StableCollectionError> for CommonError {
+    fn from(error: StableCollectionError) -> Self {
+        CommonError::StableCollectionError(error)
+    }
+}
+
+impl From<StateDecodeError> for CommonError {
+    fn from(error: StateDecodeError) -> Self {
+        CommonError::StateDecodeError(error)
+    }
+}
+
+impl From<StateTransferError> for CommonError {
+    fn from(error: StateTransferError) -> Self {
+        CommonError::StateTransferError(error)
+    }
+}
+
+impl From<StateSnapshotError> for CommonError {
+    fn from(error: StateSnapshotError) -> Self {
+        CommonError::StateSnapshotError(error)
+    }
+}
+
+impl From<CodecError> for CommonError {
+    fn from(error: CodecError) -> Self {
+        CommonError::CodecError(error)
+    }
+}
+
 impl From<ErrorInfo> for CommonError {
     fn from(error: ErrorInfo) -> Self {
         CommonError::RemoteError(error)
     }
 }
 
-/// When export_service, actor responses will merged by enum type, so if there is two response with same Ok type, the second response will be ignored.
-/// So there is no need to create more than one response type for two boolean ok.
-#[derive(CandidType)]
-pub enum BooleanActorResponse {
-    Ok(bool),
+/// Result of an actor method, encoded as `variant { Ok : T; Err : ErrorInfo }`.
+///
+/// `export_service!` names every instantiation of a generic type after the generic type, numbered
+/// in the order they are found (`ActorResponse`, `ActorResponse_1`, ...). Return a type declared
+/// with [actor_response] instead, so that the candid name does not depend on the other endpoints.
+#[derive(Debug, Clone, Eq, PartialEq, CandidType, Deserialize)]
+pub enum ActorResponse<T> {
+    Ok(T),
     Err(ErrorInfo),
 }
 
-impl BooleanActorResponse {
-    pub fn new(result: ServiceResult<bool>) -> BooleanActorResponse {
+impl<T> ActorResponse<T> {
+    pub fn new(result: ServiceResult<T>) -> ActorResponse<T> {
         match result {
-            Ok(available) => BooleanActorResponse::Ok(available),
-            Err(err) => BooleanActorResponse::Err(err.into()),
+            Ok(value) => ActorResponse::Ok(value),
+            Err(err) => ActorResponse::Err(err.into()),
+        }
+    }
+
+    pub fn into_result(self) -> ActorResult<T> {
+        match self {
+            ActorResponse::Ok(value) => Ok(value),
+            ActorResponse::Err(err) => Err(err),
         }
     }
 }
+
+impl<T> From<ServiceResult<T>> for ActorResponse<T> {
+    fn from(result: ServiceResult<T>) -> Self {
+        ActorResponse::new(result)
+    }
+}
+
+/// Declares a named [ActorResponse], the candid type is the same variant but named after the
+/// declared type.
+///
+/// ```ignore
+/// common::actor_response!(pub GetNamesResponse(Vec<String>));
+///
+/// fn get_names() -> GetNamesResponse {
+///     GetNamesResponse::new(Ok(vec![]))
+/// }
+/// ```
+///
+/// `GetNamesResponse::Ok(..)` and `GetNamesResponse::Err(..)` still construct the response like
+/// the variants of the enums declared before, match on the wrapped [ActorResponse] instead.
+///
+/// The response decodes like the wrapped [ActorResponse], callers can decode the named type. The
+/// invoking crate needs `serde` as a dependency.
+#[macro_export]
+macro_rules! actor_response {
+    ($(#[$attr:meta])* $vis:vis $name:ident($ok:ty)) => {
+        $(#[$attr])*
+        $vis struct $name(pub $crate::errors::ActorResponse<$ok>);
+
+        impl $name {
+            pub fn new(result: $crate::errors::ServiceResult<$ok>) -> $name {
+                $name($crate::errors::ActorResponse::new(result))
+            }
+
+            pub fn into_result(self) -> $crate::errors::ActorResult<$ok> {
+                self.0.into_result()
+            }
+
+            pub fn localize(self, locale: ::core::option::Option<&str>) -> $name {
+                $name(self.0.localize(locale))
+            }
+
+            #[allow(non_snake_case)]
+            pub fn Ok(value: $ok) -> $name {
+                $name($crate::errors::ActorResponse::Ok(value))
+            }
+
+            #[allow(non_snake_case)]
+            pub fn Err(err: $crate::errors::ErrorInfo) -> $name {
+                $name($crate::errors::ActorResponse::Err(err))
+            }
+        }
+
+        impl ::core::convert::From<$crate::errors::ServiceResult<$ok>> for $name {
+            fn from(result: $crate::errors::ServiceResult<$ok>) -> Self {
+                $name::new(result)
+            }
+        }
+
+        impl ::core::convert::From<$crate::errors::ActorResponse<$ok>> for $name {
+            fn from(response: $crate::errors::ActorResponse<$ok>) -> Self {
+                $name(response)
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::errors::ActorResponse<$ok> {
+            fn from(response: $name) -> Self {
+                response.0
+            }
+        }
+
+        impl ::candid::CandidType for $name {
+            fn id() -> ::candid::types::TypeId {
+                ::candid::types::TypeId::of::<$name>()
+            }
+
+            fn _ty() -> ::candid::types::Type {
+                <$crate::errors::ActorResponse<$ok> as ::candid::CandidType>::_ty()
+            }
+
+            fn idl_serialize<S>(&self, serializer: S) -> ::core::result::Result<(), S::Error>
+            where
+                S: ::candid::types::Serializer,
+            {
+                ::candid::CandidType::idl_serialize(&self.0, serializer)
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: ::serde::Deserializer<'de>,
+            {
+                <$crate::errors::ActorResponse<$ok> as ::serde::Deserialize<'de>>::deserialize(
+                    deserializer,
+                )
+                .map($name)
+            }
+        }
+    };
+}
+
+actor_response!(pub BooleanActorResponse(bool));
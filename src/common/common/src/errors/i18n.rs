@@ -0,0 +1,162 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::errors::{ActorResponse, ErrorInfo};
+use crate::http::HttpRequest;
+
+#[cfg(test)]
+mod tests;
+
+/// Locale of the `thiserror` messages, used when no template matches the requested locale.
+pub const DEFAULT_LOCALE: &str = "en";
+
+thread_local! {
+    static MESSAGE_CATALOG: RefCell<MessageCatalog> = RefCell::new(MessageCatalog::default());
+    static REQUEST_LOCALE: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Message templates per locale, keyed by full error code.
+///
+/// Templates refer to the details of an error by name, e.g. `{field}` in the template of
+/// `ValueShouldBeInRangeError`. Placeholders without a matching detail are kept as they are.
+#[derive(Debug, Clone, Default)]
+pub struct MessageCatalog {
+    locales: BTreeMap<String, BTreeMap<u32, String>>,
+}
+
+impl MessageCatalog {
+    /// Adds the templates of `locale`, replacing existing templates of the same codes.
+    pub fn add_templates(&mut self, locale: &str, templates: &[(u32, &str)]) {
+        let entries = self.locales.entry(normalize_locale(locale)).or_default();
+        for (code, template) in templates {
+            entries.insert(*code, template.to_string());
+        }
+    }
+
+    pub fn locales(&self) -> Vec<String> {
+        self.locales.keys().cloned().collect()
+    }
+
+    /// Returns the template of `code`, looking up `locale` first and then its language,
+    /// e.g. `zh-CN` and then `zh`.
+    pub fn template(&self, locale: &str, code: u32) -> Option<&str> {
+        let locale = normalize_locale(locale);
+        let language = locale.split('-').next().unwrap_or_default();
+        [locale.as_str(), language]
+            .iter()
+            .find_map(|locale| self.locales.get(*locale)?.get(&code))
+            .map(|template| template.as_str())
+    }
+
+    /// Returns the message of `info` in `locale`, or its original message if there is no template.
+    pub fn message(&self, info: &ErrorInfo, locale: Option<&str>) -> String {
+        locale
+            .and_then(|locale| self.template(locale, info.code))
+            .map(|template| fill_template(template, info.details.as_ref()))
+            .unwrap_or_else(|| info.message.clone())
+    }
+
+    /// Returns the first locale of an `Accept-Language` header with templates, by quality.
+    pub fn negotiate(&self, accept_language: &str) -> Option<String> {
+        parse_accept_language(accept_language)
+            .into_iter()
+            .find(|locale| {
+                let language = locale.split('-').next().unwrap_or_default();
+                self.locales.contains_key(locale) || self.locales.contains_key(language)
+            })
+    }
+}
+
+/// Adds templates to the catalog of the canister, usually in `init` and `post_upgrade`.
+pub fn register_messages(locale: &str, templates: &[(u32, &str)]) {
+    MESSAGE_CATALOG.with(|catalog| catalog.borrow_mut().add_templates(locale, templates));
+}
+
+pub fn with_message_catalog<F, R>(f: F) -> R
+where
+    F: FnOnce(&MessageCatalog) -> R,
+{
+    MESSAGE_CATALOG.with(|catalog| f(&catalog.borrow()))
+}
+
+/// Returns the locale of `request` from its `Accept-Language` header, `None` if no locale
+/// of the header has templates.
+pub fn request_locale(request: &HttpRequest) -> Option<String> {
+    let accept_language = request.get_header("Accept-Language")?;
+    with_message_catalog(|catalog| catalog.negotiate(&accept_language))
+}
+
+/// Runs `f` with `locale` as the locale of error responses built by
+/// [crate::http::HttpResponse::from_error], e.g. the [request_locale] of an http request.
+pub fn with_request_locale<F, R>(locale: Option<String>, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = REQUEST_LOCALE.with(|current| current.replace(locale));
+    let result = f();
+    REQUEST_LOCALE.with(|current| current.replace(previous));
+    result
+}
+
+/// The locale set by [with_request_locale], `None` outside of it.
+pub fn current_request_locale() -> Option<String> {
+    REQUEST_LOCALE.with(|current| current.borrow().clone())
+}
+
+impl ErrorInfo {
+    /// Replaces the message with the template of `locale` registered by [register_messages].
+    ///
+    /// The message is kept when `locale` is `None` or has no template for the code.
+    pub fn localize(mut self, locale: Option<&str>) -> Self {
+        self.message = with_message_catalog(|catalog| catalog.message(&self, locale));
+        self
+    }
+}
+
+impl<T> ActorResponse<T> {
+    pub fn localize(self, locale: Option<&str>) -> Self {
+        match self {
+            ActorResponse::Err(err) => ActorResponse::Err(err.localize(locale)),
+            ok => ok,
+        }
+    }
+}
+
+/// Returns the locales of an `Accept-Language` header, ordered by quality. `*` is skipped.
+pub fn parse_accept_language(header: &str) -> Vec<String> {
+    let mut locales: Vec<(u32, usize, String)> = header
+        .split(',')
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let mut parts = item.split(';');
+            let locale = parts.next()?.trim();
+            if locale.is_empty() || locale == "*" {
+                return None;
+            }
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |quality| quality.trim().parse::<f32>().ok())?;
+            if quality <= 0.0 {
+                return None;
+            }
+            // sort by descending quality, then by position in the header
+            let rank = u32::MAX - (quality.min(1.0) * 1000.0) as u32;
+            Some((rank, index, normalize_locale(locale)))
+        })
+        .collect();
+    locales.sort();
+    locales.into_iter().map(|(_, _, locale)| locale).collect()
+}
+
+/// `zh_CN` and `ZH-cn` are both normalized to `zh-cn`.
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+fn fill_template(template: &str, details: Option<&BTreeMap<String, String>>) -> String {
+    let mut message = template.to_string();
+    for (key, value) in details.into_iter().flatten() {
+        message = message.replace(&format!("{{{}}}", key), value);
+    }
+    message
+}
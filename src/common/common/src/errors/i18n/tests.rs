@@ -0,0 +1,81 @@
+use rstest::*;
+
+use super::*;
+use crate::errors::{get_error_code, CommonError};
+use crate::http::HeaderField;
+use crate::test_common::test::init_test;
+
+#[fixture]
+fn catalog() -> MessageCatalog {
+    init_test();
+    let mut catalog = MessageCatalog::default();
+    catalog.add_templates("zh", &[(5, "{field} 的长度必须在 [{min}, {max}) 之间")]);
+    catalog.add_templates("zh-TW", &[(5, "{field} 的長度必須在 [{min}, {max}) 之間")]);
+    catalog
+}
+
+fn range_error() -> ErrorInfo {
+    get_error_code(CommonError::ValueShouldBeInRangeError {
+        field: "limit".to_string(),
+        min: 1,
+        max: 100,
+    })
+}
+
+#[rstest]
+#[case(Some("zh"), "limit 的长度必须在 [1, 100) 之间")]
+#[case(Some("zh-CN"), "limit 的长度必须在 [1, 100) 之间")]
+#[case(Some("zh_TW"), "limit 的長度必須在 [1, 100) 之間")]
+#[case(Some("fr"), "Length of \"limit\" must be in range [1, 100)")]
+#[case(None, "Length of \"limit\" must be in range [1, 100)")]
+fn test_message(catalog: MessageCatalog, #[case] locale: Option<&str>, #[case] expected: &str) {
+    assert_eq!(catalog.message(&range_error(), locale), expected);
+}
+
+#[rstest]
+fn test_message_keeps_unknown_placeholders(mut catalog: MessageCatalog) {
+    catalog.add_templates("zh", &[(4, "{who} 没有权限")]);
+    let info = get_error_code(CommonError::PermissionDenied);
+    assert_eq!(catalog.message(&info, Some("zh")), "{who} 没有权限");
+}
+
+#[rstest]
+#[case("zh-CN,zh;q=0.9,en;q=0.8", vec!["zh-cn", "zh", "en"])]
+#[case("en;q=0.5, fr, *", vec!["fr", "en"])]
+#[case("de;q=0, zh_TW", vec!["zh-tw"])]
+#[case("", vec![])]
+fn test_parse_accept_language(#[case] header: &str, #[case] expected: Vec<&str>) {
+    assert_eq!(parse_accept_language(header), expected);
+}
+
+#[rstest]
+#[case("fr, zh-CN;q=0.8", Some("zh-cn"))]
+#[case("fr, en", None)]
+fn test_negotiate(catalog: MessageCatalog, #[case] header: &str, #[case] expected: Option<&str>) {
+    assert_eq!(catalog.negotiate(header), expected.map(|s| s.to_string()));
+}
+
+#[rstest]
+fn test_localize_with_registered_messages() {
+    init_test();
+    register_messages("zh", &[(3, "未授权，请先登录")]);
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: "/".to_string(),
+        headers: vec![HeaderField(
+            "accept-language".to_string(),
+            "zh-CN,zh;q=0.9".to_string(),
+        )],
+        body: vec![],
+    };
+    let locale = request_locale(&request);
+    assert_eq!(locale, Some("zh-cn".to_string()));
+
+    let response = ActorResponse::<bool>::new(Err(CommonError::Unauthorized));
+    let err = response
+        .localize(locale.as_deref())
+        .into_result()
+        .unwrap_err();
+    assert_eq!(err.code, 3);
+    assert_eq!(err.message, "未授权，请先登录");
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeSet;
+
+use candid::types::TypeId;
+use candid::{decode_one, encode_one};
+use rstest::*;
+
+use super::*;
+use crate::test_common::test::init_test;
+
+#[rstest]
+fn test_actor_response() {
+    init_test();
+    assert_eq!(ActorResponse::new(Ok(1u64)).into_result(), Ok(1u64));
+    let err = ActorResponse::<u64>::new(Err(CommonError::PermissionDenied))
+        .into_result()
+        .unwrap_err();
+    assert_eq!(err.code, 4);
+    assert_eq!(err.message, "Permission denied");
+    assert_eq!(err.details, None);
+    assert!(err.trace_id.is_some());
+}
+
+#[rstest]
+fn test_named_actor_response_encodes_as_actor_response() {
+    init_test();
+    let bytes = encode_one(BooleanActorResponse::new(Ok(true))).unwrap();
+    let decoded: ActorResponse<bool> = decode_one(&bytes).unwrap();
+    assert_eq!(decoded, ActorResponse::Ok(true));
+
+    let bytes = encode_one(BooleanActorResponse::new(Err(CommonError::Unauthorized))).unwrap();
+    let decoded: ActorResponse<bool> = decode_one(&bytes).unwrap();
+    assert_eq!(decoded.into_result().unwrap_err().code, 3);
+}
+
+#[rstest]
+fn test_named_actor_response_decodes() {
+    init_test();
+    let bytes = encode_one(BooleanActorResponse::new(Ok(true))).unwrap();
+    let decoded: BooleanActorResponse = decode_one(&bytes).unwrap();
+    assert_eq!(decoded.0, ActorResponse::Ok(true));
+
+    let bytes = encode_one(ActorResponse::<bool>::new(Err(CommonError::Unauthorized))).unwrap();
+    let decoded: BooleanActorResponse = decode_one(&bytes).unwrap();
+    assert_eq!(decoded.into_result().unwrap_err().code, 3);
+}
+
+#[rstest]
+fn test_named_actor_response_variant_constructors() {
+    init_test();
+    let response = BooleanActorResponse::Ok(true);
+    assert_eq!(ActorResponse::from(response), ActorResponse::Ok(true));
+
+    let info = ErrorInfo::new(3, "Unauthorized".to_string());
+    let response: ActorResponse<bool> = BooleanActorResponse::Err(info.clone()).into();
+    match response {
+        ActorResponse::Err(err) => assert_eq!(err, info),
+        ActorResponse::Ok(_) => panic!("expected an error"),
+    }
+
+    let response = BooleanActorResponse::from(ActorResponse::Ok(false));
+    assert_eq!(response.into_result(), Ok(false));
+}
+
+#[rstest]
+fn test_named_actor_response_type_name() {
+    assert_eq!(
+        BooleanActorResponse::id(),
+        TypeId::of::<BooleanActorResponse>()
+    );
+    assert_ne!(BooleanActorResponse::id(), ActorResponse::<bool>::id());
+    assert_eq!(BooleanActorResponse::_ty(), ActorResponse::<bool>::_ty());
+}
+
+#[rstest]
+#[case(CommonError::RemoteError(ErrorInfo::new(1, "".to_string())), 2)]
+#[case(CommonError::Unauthorized, 3)]
+#[case(CommonError::PermissionDenied, 4)]
+#[case(CommonError::StateCryptoError { detail: "".to_string() }, 11)]
+#[case(CommonError::Unknown { detail: "".to_string() }, 10000)]
+fn test_common_error_codes_are_stable(#[case] error: CommonError, #[case] code: u32) {
+    assert_eq!(error.code(), code);
+}
+
+#[rstest]
+fn test_common_error_catalog() {
+    let catalog = CommonError::catalog();
+    let codes: BTreeSet<u32> = catalog.iter().map(|entry| entry.code).collect();
+    let names: BTreeSet<&str> = catalog.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(codes.len(), catalog.len());
+    assert_eq!(names.len(), catalog.len());
+    assert!(catalog.iter().all(|entry| entry.namespace == "common"));
+
+    let entry = |code: u32| catalog.iter().find(|entry| entry.code == code).unwrap();
+    assert_eq!(
+        entry(4),
+        &ErrorCodeEntry {
+            code: 4,
+            namespace: "common".to_string(),
+            name: "PermissionDenied".to_string(),
+            message: "Permission denied".to_string(),
+        }
+    );
+    assert_eq!(entry(14).name, "HttpRouteNotFound");
+    assert_eq!(entry(16).name, "InvalidStreamingToken");
+    assert_eq!(entry(17).name, "InvalidUrl");
+    assert_eq!(entry(10000).name, "Unknown");
+}
+
+#[rstest]
+#[should_panic(expected = "error code 4 is used by both")]
+fn test_merge_error_catalogs_rejects_duplicated_codes() {
+    merge_error_catalogs(vec![CommonError::catalog(), CommonError::catalog()]);
+}
+
+#[rstest]
+fn test_error_info_details() {
+    init_test();
+    let info = get_error_code(CommonError::ValueShouldBeInRangeError {
+        field: "limit".to_string(),
+        min: 1,
+        max: 100,
+    });
+    assert_eq!(
+        info.details,
+        Some(BTreeMap::from([
+            ("field".to_string(), "limit".to_string()),
+            ("max".to_string(), "100".to_string()),
+            ("min".to_string(), "1".to_string()),
+        ]))
+    );
+
+    let info = get_error_code(CommonError::RemoteError(ErrorInfo::new(
+        2,
+        "remote".to_string(),
+    )));
+    assert_eq!(
+        info.details,
+        Some(BTreeMap::from([("0".to_string(), "2 remote".to_string())]))
+    );
+}
+
+#[rstest]
+fn test_error_info_trace_ids_are_unique() {
+    init_test();
+    let first = get_error_code(CommonError::Unauthorized);
+    let second = get_error_code(CommonError::Unauthorized);
+    assert_ne!(first.trace_id, second.trace_id);
+
+    let info = first.with_trace_id("request-1".to_string());
+    assert_eq!(info.trace_id, Some("request-1".to_string()));
+}
+
+#[derive(Debug, Eq, PartialEq, CandidType, Deserialize)]
+struct ErrorInfoV1 {
+    code: u32,
+    message: String,
+}
+
+#[rstest]
+fn test_error_info_is_compatible_with_old_clients() {
+    init_test();
+    let info = get_error_code(CommonError::StateCryptoError {
+        detail: "bad key".to_string(),
+    });
+    let decoded: ErrorInfoV1 = decode_one(&encode_one(&info).unwrap()).unwrap();
+    assert_eq!(
+        decoded,
+        ErrorInfoV1 {
+            code: 11,
+            message: info.message.clone(),
+        }
+    );
+
+    let old = encode_one(ErrorInfoV1 {
+        code: 3,
+        message: "Unauthorized, please login first".to_string(),
+    })
+    .unwrap();
+    let decoded: ErrorInfo = decode_one(&old).unwrap();
+    assert_eq!(
+        decoded,
+        ErrorInfo::new(3, "Unauthorized, please login first".to_string())
+    );
+}
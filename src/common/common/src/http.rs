@@ -10,6 +10,16 @@ use candid::{CandidType, Deserialize, Func, Nat};
 use serde_bytes::ByteBuf;
 use url::Url;
 
+use crate::errors::{CommonError, ServiceResult};
+
+pub use problem::{HttpResult, ProblemDocument};
+pub use router::{RouteRequest, Router};
+
+pub mod certification;
+pub mod problem;
+pub mod router;
+pub mod streaming;
+
 #[cfg(test)]
 mod tests;
 
@@ -25,7 +35,7 @@ pub struct HttpRequest {
     pub body: Vec<u8>,
 }
 
-#[derive(CandidType, Deserialize)]
+#[derive(CandidType, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct Token {
     key: String,
     content_encoding: String,
@@ -85,21 +95,44 @@ impl HttpResponse {
 }
 
 impl HttpRequest {
+    /// Returns the first value of the query parameter `name`, `None` if the url is malformed.
     pub fn get_query_value(&self, name: &str) -> Option<String> {
-        let url = self.get_url();
+        let url = self.parse_url().ok()?;
         get_query_value(&url, name)
     }
 
+    /// Returns all values of the query parameter `name`, none if the url is malformed.
     pub fn get_query_values(&self, name: &str) -> Vec<String> {
-        let url = self.get_url();
-        get_query_values(&url, name)
+        match self.parse_url() {
+            Ok(url) => get_query_values(&url, name),
+            Err(_) => vec![],
+        }
     }
 
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn get_header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|header| header.0.eq_ignore_ascii_case(name))
+            .map(|header| header.1.clone())
+    }
+
+    /// Parses the url of the request, relative to the canister.
+    ///
+    /// Traps if the url is malformed, use [HttpRequest::parse_url] for urls sent by clients.
     pub fn get_url(&self) -> Url {
+        self.parse_url()
+            .unwrap_or_else(|err| ic_cdk::trap(&err.to_string()))
+    }
+
+    /// Parses the url of the request, relative to the canister.
+    pub fn parse_url(&self) -> ServiceResult<Url> {
         Url::parse("http://localhost")
-            .unwrap()
-            .join(self.url.as_str())
-            .unwrap()
+            .and_then(|base| base.join(self.url.as_str()))
+            .map_err(|err| CommonError::InvalidUrl {
+                url: self.url.clone(),
+                detail: err.to_string(),
+            })
     }
 }
 
@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+
+use ic_certified_map::{labeled, labeled_hash, AsHashTree, Hash, RbTree};
+use log::debug;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::http::{HeaderField, HttpRequest, HttpResponse, Router, StreamingStrategy};
+
+#[cfg(test)]
+mod tests;
+
+/// Label of the response hashes in the certified tree, the one boundary nodes look up.
+const CERTIFIED_RESPONSES_LABEL: &[u8] = b"http_assets";
+
+pub const CERTIFICATE_HEADER: &str = "IC-Certificate";
+
+thread_local! {
+    static CERTIFIED_RESPONSES: RefCell<CertifiedResponses> =
+        RefCell::new(CertifiedResponses::default());
+}
+
+/// Hashes of the response bodies of certified paths.
+///
+/// A query can not change the certified data, so responses must be certified in an update call,
+/// `init` or `post_upgrade` and served unchanged by `http_request`. The tree is not kept in stable
+/// memory, certify the responses again in `post_upgrade`.
+#[derive(Default)]
+pub struct CertifiedResponses {
+    tree: RbTree<String, Hash>,
+}
+
+impl CertifiedResponses {
+    pub fn insert(&mut self, path: &str, body: &[u8]) {
+        self.insert_hash(path, body_hash(body));
+    }
+
+    /// Certifies the body of `path` by its hash, see [response_hash].
+    pub fn insert_hash(&mut self, path: &str, hash: Hash) {
+        self.tree.insert(path.to_string(), hash);
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        self.tree.delete(path.as_bytes());
+    }
+
+    /// Whether `body` is the certified response of `path`.
+    pub fn is_certified(&self, path: &str, body: &[u8]) -> bool {
+        self.is_certified_hash(path, &body_hash(body))
+    }
+
+    pub fn is_certified_hash(&self, path: &str, hash: &Hash) -> bool {
+        self.tree.get(path.as_bytes()) == Some(hash)
+    }
+
+    /// Hash to set as the certified data of the canister.
+    pub fn certified_data(&self) -> Hash {
+        labeled_hash(CERTIFIED_RESPONSES_LABEL, &self.tree.root_hash())
+    }
+
+    /// Returns the value of the [CERTIFICATE_HEADER] of `path`, with the witness of its hash.
+    pub fn certificate_header(&self, path: &str, certificate: &[u8]) -> String {
+        let tree = labeled(
+            CERTIFIED_RESPONSES_LABEL,
+            self.tree.witness(path.as_bytes()),
+        );
+        let mut serializer = serde_cbor::ser::Serializer::new(vec![]);
+        serializer.self_describe().unwrap();
+        tree.serialize(&mut serializer)
+            .expect("failed to encode certified tree");
+        format!(
+            "certificate=:{}:, tree=:{}:",
+            base64::encode(certificate),
+            base64::encode(serializer.into_inner())
+        )
+    }
+}
+
+fn body_hash(body: &[u8]) -> Hash {
+    Sha256::digest(body).into()
+}
+
+/// Hash of the whole body of `response`.
+///
+/// A streamed response only holds its first chunk, the hash of the whole body is taken from the
+/// streaming token, see [HttpResponse::streamed].
+pub fn response_hash(response: &HttpResponse) -> Hash {
+    let streamed_hash = match &response.streaming_strategy {
+        Some(StreamingStrategy::Callback(strategy)) => strategy.token.sha256(),
+        None => None,
+    };
+    streamed_hash.unwrap_or_else(|| body_hash(&response.body))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn set_certified_data(data: &Hash) {
+    ic_cdk::api::set_certified_data(data);
+}
+
+/// Outside of a canister there is no certified data to set.
+#[cfg(not(target_arch = "wasm32"))]
+fn set_certified_data(_data: &Hash) {}
+
+#[cfg(target_arch = "wasm32")]
+fn data_certificate() -> Option<Vec<u8>> {
+    ic_cdk::api::data_certificate()
+}
+
+/// Outside of a canister the certificate is empty, so that the headers can be tested.
+#[cfg(not(target_arch = "wasm32"))]
+fn data_certificate() -> Option<Vec<u8>> {
+    Some(vec![])
+}
+
+/// Certifies `body` as the response of `path` and updates the certified data.
+///
+/// Static routes are certified in `init` and `post_upgrade`, dynamic routes whenever an update
+/// call changes their response.
+pub fn certify_response(path: &str, body: &[u8]) {
+    CERTIFIED_RESPONSES.with(|responses| {
+        let mut responses = responses.borrow_mut();
+        responses.insert(path, body);
+        set_certified_data(&responses.certified_data());
+    });
+}
+
+/// Certifies the response `router` gives to a `GET` of `path`, in an update call, `init` or
+/// `post_upgrade`.
+///
+/// This works for dynamic routes as long as the handler renders the same body in the query: call it
+/// again whenever an update changes the response of `path`. Error responses are certified too,
+/// their body does not contain the trace id.
+pub fn certify_route(router: &Router, path: &str) {
+    let request = HttpRequest {
+        method: "GET".to_string(),
+        url: path.to_string(),
+        headers: vec![],
+        body: vec![],
+    };
+    let response = router.handle(&request);
+    CERTIFIED_RESPONSES.with(|responses| {
+        let mut responses = responses.borrow_mut();
+        responses.insert_hash(path, response_hash(&response));
+        set_certified_data(&responses.certified_data());
+    });
+}
+
+pub fn remove_certified_response(path: &str) {
+    CERTIFIED_RESPONSES.with(|responses| {
+        let mut responses = responses.borrow_mut();
+        responses.remove(path);
+        set_certified_data(&responses.certified_data());
+    });
+}
+
+/// Attaches the [CERTIFICATE_HEADER] to the response of `path` if its body is certified.
+///
+/// Only works in a query, where the certificate of the certified data is available.
+pub fn add_certificate_header(path: &str, response: &mut HttpResponse) {
+    let certificate = match data_certificate() {
+        Some(certificate) => certificate,
+        None => return,
+    };
+    CERTIFIED_RESPONSES.with(|responses| {
+        let responses = responses.borrow();
+        if !responses.is_certified_hash(path, &response_hash(response)) {
+            debug!("response of {} is not certified", path);
+            return;
+        }
+        response.headers.push(HeaderField(
+            CERTIFICATE_HEADER.to_string(),
+            responses.certificate_header(path, &certificate),
+        ));
+    });
+}
+
+/// A [crate::http::Router] hook certifying responses, `router.after(certify_hook)`.
+///
+/// Responses to malformed urls are never certified.
+pub fn certify_hook(request: &HttpRequest, response: &mut HttpResponse) {
+    if let Ok(url) = request.parse_url() {
+        add_certificate_header(url.path(), response);
+    }
+}
@@ -0,0 +1,134 @@
+use candid::{Func, Principal};
+use rstest::*;
+
+use super::*;
+use crate::constants::HTTP_STREAMING_CHUNK_SIZE;
+use crate::errors::CommonError;
+use crate::test_common::test::init_test;
+
+#[fixture]
+fn responses() -> CertifiedResponses {
+    let mut responses = CertifiedResponses::default();
+    responses.insert("/", b"index");
+    responses.insert("/names/hello", b"hello");
+    responses
+}
+
+#[rstest]
+fn test_is_certified(responses: CertifiedResponses) {
+    assert!(responses.is_certified("/", b"index"));
+    assert!(responses.is_certified("/names/hello", b"hello"));
+    assert!(!responses.is_certified("/names/hello", b"changed"));
+    assert!(!responses.is_certified("/names/other", b"hello"));
+}
+
+#[rstest]
+fn test_certified_data_follows_responses(mut responses: CertifiedResponses) {
+    let certified_data = responses.certified_data();
+
+    responses.insert("/names/hello", b"changed");
+    assert_ne!(responses.certified_data(), certified_data);
+    assert!(responses.is_certified("/names/hello", b"changed"));
+
+    responses.insert("/names/hello", b"hello");
+    assert_eq!(responses.certified_data(), certified_data);
+
+    responses.remove("/names/hello");
+    assert!(!responses.is_certified("/names/hello", b"hello"));
+    assert_ne!(responses.certified_data(), certified_data);
+}
+
+#[rstest]
+fn test_certificate_header(responses: CertifiedResponses) {
+    let header = responses.certificate_header("/names/hello", &[1, 2, 3]);
+    let (certificate, tree) = header
+        .strip_prefix("certificate=:")
+        .and_then(|header| header.strip_suffix(':'))
+        .and_then(|header| header.split_once(":, tree=:"))
+        .unwrap();
+    assert_eq!(base64::decode(certificate).unwrap(), vec![1, 2, 3]);
+
+    let tree = base64::decode(tree).unwrap();
+    // self-describe tag of CBOR
+    assert_eq!(tree[..3], [0xd9, 0xd9, 0xf7]);
+    let tree: serde_cbor::Value = serde_cbor::from_slice(&tree).unwrap();
+    assert!(matches!(tree, serde_cbor::Value::Array(_)));
+}
+
+fn get(url: &str) -> HttpRequest {
+    HttpRequest {
+        method: "GET".to_string(),
+        url: url.to_string(),
+        headers: vec![],
+        body: vec![],
+    }
+}
+
+fn certificate_header(response: &HttpResponse) -> Option<&str> {
+    response
+        .headers
+        .iter()
+        .find(|header| header.0 == CERTIFICATE_HEADER)
+        .map(|header| header.1.as_str())
+}
+
+fn large_body() -> Vec<u8> {
+    vec![7; HTTP_STREAMING_CHUNK_SIZE + 1]
+}
+
+fn router() -> Router {
+    Router::new()
+        .get("/names/{name}", |request| {
+            let name = request.param("name").unwrap();
+            if name == "missing" {
+                return Err(CommonError::HttpRouteNotFound {
+                    path: request.url.path().to_string(),
+                }
+                .into());
+            }
+            Ok(HttpResponse::string(200, name))
+        })
+        .get("/large", |_| {
+            let callback = Func {
+                principal: Principal::anonymous(),
+                method: "http_request_streaming_callback".to_string(),
+            };
+            Ok(HttpResponse::streamed(200, "large", large_body(), callback))
+        })
+        .after(certify_hook)
+}
+
+#[rstest]
+#[case("/names/hello")]
+#[case("/names/missing")]
+#[case("/large")]
+fn test_certified_route_gets_certificate_header(#[case] path: &str) {
+    init_test();
+    let router = router();
+    assert_eq!(certificate_header(&router.handle(&get(path))), None);
+
+    certify_route(&router, path);
+    let response = router.handle(&get(path));
+    assert!(certificate_header(&response)
+        .unwrap()
+        .starts_with("certificate=:"));
+}
+
+#[rstest]
+fn test_changed_response_is_not_certified() {
+    init_test();
+    let router = router();
+    certify_route(&router, "/names/hello");
+    certify_response("/names/hello", b"changed");
+
+    let response = router.handle(&get("/names/hello"));
+    assert_eq!(certificate_header(&response), None);
+}
+
+#[rstest]
+fn test_streamed_response_is_certified_by_whole_body() {
+    init_test();
+    let response = router().handle(&get("/large"));
+    assert_eq!(response.body.len(), HTTP_STREAMING_CHUNK_SIZE);
+    assert_eq!(response_hash(&response), body_hash(&large_body()));
+}
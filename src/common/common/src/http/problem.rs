@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde::Serialize;
+
+use crate::errors::i18n::current_request_locale;
+use crate::errors::{CommonError, ErrorCode, ErrorInfo};
+use crate::http::{HeaderField, HttpResponse};
+
+#[cfg(test)]
+mod tests;
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+/// Header carrying the trace id of an error response.
+pub const TRACE_ID_HEADER: &str = "X-Trace-Id";
+
+/// Result of an `http_request` handler, errors are already converted to responses so that
+/// service errors can be returned with `?`.
+///
+/// ```ignore
+/// fn get_stats(request: &HttpRequest) -> HttpResult {
+///     let stats = load_stats()?;
+///     Ok(HttpResponse::string(200, &stats.to_string()))
+/// }
+///
+/// get_stats(&request).unwrap_or_else(|response| response)
+/// ```
+pub type HttpResult = Result<HttpResponse, HttpResponse>;
+
+/// JSON problem document of an error response, members of RFC 7807 plus the fields of [ErrorInfo].
+///
+/// The trace id is sent in the [TRACE_ID_HEADER] instead, so that the body only depends on the
+/// error and can be certified, see [crate::http::certification].
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct ProblemDocument {
+    pub title: String,
+    pub status: u16,
+    pub code: u32,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<BTreeMap<String, String>>,
+}
+
+impl ProblemDocument {
+    pub fn new(status: u16, info: ErrorInfo) -> Self {
+        Self {
+            title: reason_phrase(status).to_string(),
+            status,
+            code: info.code,
+            message: info.message,
+            details: info.details,
+        }
+    }
+}
+
+impl HttpResponse {
+    /// Returns the problem document of `error` with the status of the error code.
+    ///
+    /// The message is localized to the locale of the request handled by the [crate::http::Router],
+    /// see [crate::errors::i18n::with_request_locale].
+    pub fn from_error<E: ErrorCode + Display>(error: &E) -> HttpResponse {
+        let locale = current_request_locale();
+        HttpResponse::problem(
+            error.http_status(),
+            ErrorInfo::from_error(error).localize(locale.as_deref()),
+        )
+    }
+
+    /// Returns `info` as a problem document, e.g. after [ErrorInfo::localize].
+    pub fn problem(status_code: u16, info: ErrorInfo) -> HttpResponse {
+        let trace_id = info.trace_id.clone();
+        let body = serde_json::to_vec(&ProblemDocument::new(status_code, info))
+            .expect("failed to encode problem document");
+        let mut response = HttpResponse::new(status_code, body);
+        response.headers.push(HeaderField(
+            "Content-Type".to_string(),
+            PROBLEM_CONTENT_TYPE.to_string(),
+        ));
+        if let Some(trace_id) = trace_id {
+            response
+                .headers
+                .push(HeaderField(TRACE_ID_HEADER.to_string(), trace_id));
+        }
+        response
+    }
+}
+
+impl From<CommonError> for HttpResponse {
+    fn from(error: CommonError) -> Self {
+        HttpResponse::from_error(&error)
+    }
+}
+
+/// Reason phrase of the statuses used by error responses.
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        400..=499 => "Client Error",
+        _ => "Server Error",
+    }
+}
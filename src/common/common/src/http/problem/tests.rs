@@ -0,0 +1,98 @@
+use rstest::*;
+use serde_json::{json, Value};
+
+use super::*;
+use crate::canister_api::reject::RejectCode;
+use crate::state::collections::StableCollectionError;
+use crate::state::snapshots::StateSnapshotError;
+use crate::state::transfer::StateTransferError;
+use crate::state::StateDecodeError;
+use crate::test_common::test::init_test;
+
+fn body_json(response: &HttpResponse) -> Value {
+    serde_json::from_slice(&response.body).unwrap()
+}
+
+#[rstest]
+#[case(CommonError::Unauthorized, 401)]
+#[case(CommonError::PermissionDenied, 403)]
+#[case(CommonError::InvalidPageCursor { detail: "".to_string() }, 400)]
+#[case(CommonError::CanisterCallError { message: "".to_string(), rejection_code: RejectCode::SysTransient }, 502)]
+#[case(CommonError::StateCryptoError { detail: "".to_string() }, 400)]
+#[case(CommonError::StateTransferError(StateTransferError::SessionNotFound { session_id: 1 }), 404)]
+#[case(CommonError::StateTransferError(StateTransferError::ChunkHashMismatch { index: 1 }), 400)]
+#[case(CommonError::StateTransferError(StateTransferError::StateTooLarge { size: 2, max: 1 }), 413)]
+#[case(CommonError::StateSnapshotError(StateSnapshotError::NotFound { id: 1 }), 404)]
+#[case(CommonError::StateSnapshotError(StateSnapshotError::StorageError { detail: "".to_string() }), 500)]
+#[case(CommonError::StateDecodeError(StateDecodeError::ChecksumMismatch { expected: 1, actual: 2 }), 400)]
+#[case(CommonError::StateDecodeError(StateDecodeError::InvalidMagic { magic: vec![] }), 400)]
+#[case(CommonError::StateDecodeError(StateDecodeError::MigrationFailed { from_version: 1, detail: "".to_string() }), 500)]
+#[case(CommonError::StableCollectionError(StableCollectionError::ValueTooLarge { memory_id: 0, size: 2, max_size: 1 }), 413)]
+#[case(CommonError::InvalidUrl { url: "//[".to_string(), detail: "".to_string() }, 400)]
+#[case(CommonError::Unknown { detail: "".to_string() }, 500)]
+fn test_common_error_http_status(#[case] error: CommonError, #[case] status: u16) {
+    init_test();
+    assert_eq!(error.http_status(), status);
+    assert_eq!(HttpResponse::from(error).status_code, status);
+}
+
+#[rstest]
+fn test_problem_document() {
+    init_test();
+    let response = HttpResponse::from(CommonError::ValueShouldBeInRangeError {
+        field: "limit".to_string(),
+        min: 1,
+        max: 100,
+    });
+    assert_eq!(response.status_code, 400);
+    assert_eq!(response.headers[0].0, "Content-Type");
+    assert_eq!(response.headers[0].1, PROBLEM_CONTENT_TYPE);
+
+    assert_eq!(response.headers[1].0, TRACE_ID_HEADER);
+    assert_eq!(
+        body_json(&response),
+        json!({
+            "title": "Bad Request",
+            "status": 400,
+            "code": 5,
+            "message": "Length of \"limit\" must be in range [1, 100)",
+            "details": {"field": "limit", "min": "1", "max": "100"},
+        })
+    );
+}
+
+#[rstest]
+fn test_problem_body_does_not_depend_on_trace_id() {
+    init_test();
+    let first = HttpResponse::from(CommonError::PermissionDenied);
+    let second = HttpResponse::from(CommonError::PermissionDenied);
+    assert_ne!(first.headers[1].1, second.headers[1].1);
+    assert_eq!(first.body, second.body);
+}
+
+#[rstest]
+fn test_problem_document_without_details() {
+    let response = HttpResponse::problem(404, ErrorInfo::new(1, "not found".to_string()));
+    assert_eq!(
+        body_json(&response),
+        json!({"title": "Not Found", "status": 404, "code": 1, "message": "not found"})
+    );
+}
+
+fn handle(fail: bool) -> HttpResult {
+    if fail {
+        Err(CommonError::PermissionDenied)?;
+    }
+    Ok(HttpResponse::string(200, "ok"))
+}
+
+#[rstest]
+fn test_handler_returns_errors_with_question_mark() {
+    init_test();
+    let response = handle(true).unwrap_or_else(|response| response);
+    assert_eq!(response.status_code, 403);
+    assert_eq!(body_json(&response)["code"], 4);
+
+    let response = handle(false).unwrap_or_else(|response| response);
+    assert_eq!(response.status_code, 200);
+}
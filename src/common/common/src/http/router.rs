@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::errors::i18n::{request_locale, with_request_locale};
+use crate::errors::CommonError;
+use crate::http::{get_query_value, HeaderField, HttpRequest, HttpResponse, HttpResult};
+
+#[cfg(test)]
+mod tests;
+
+type Handler = Box<dyn Fn(&RouteRequest) -> HttpResult>;
+type BeforeHook = Box<dyn Fn(&HttpRequest) -> Option<HttpResponse>>;
+type AfterHook = Box<dyn Fn(&HttpRequest, &mut HttpResponse)>;
+
+/// A request matched by a route, with the parameters of the route pattern.
+pub struct RouteRequest<'a> {
+    pub request: &'a HttpRequest,
+    pub url: Url,
+    pub params: BTreeMap<String, String>,
+}
+
+impl RouteRequest<'_> {
+    /// Returns the path parameter `name`, e.g. `name` of `/names/{name}`.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|value| value.as_str())
+    }
+
+    pub fn query(&self, name: &str) -> Option<String> {
+        get_query_value(&self.url, name)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+}
+
+struct Route {
+    method: String,
+    segments: Vec<Segment>,
+    handler: Handler,
+}
+
+/// Dispatches `http_request` by method and path.
+///
+/// ```ignore
+/// thread_local! {
+///     static ROUTER: Router = Router::new()
+///         .get("/names/{name}", |req| get_name(req.param("name").unwrap()))
+///         .after(|_, response| add_cors_headers(response));
+/// }
+///
+/// #[query]
+/// fn http_request(request: HttpRequest) -> HttpResponse {
+///     ROUTER.with(|router| router.handle(&request))
+/// }
+/// ```
+///
+/// Routes are matched in registration order. A path without a route is answered with 404, a
+/// path with routes of other methods only with 405 and an `Allow` header, a malformed url with 400.
+///
+/// Problem responses are localized with the templates registered by
+/// [crate::errors::i18n::register_messages], register them in `init` and `post_upgrade`.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    before: Vec<BeforeHook>,
+    after: Vec<AfterHook>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a route, segments of `pattern` in braces such as `{name}` match any single segment.
+    pub fn route<F>(mut self, method: &str, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(&RouteRequest) -> HttpResult + 'static,
+    {
+        let segments = split_path(pattern)
+            .map(|segment| {
+                match segment
+                    .strip_prefix('{')
+                    .and_then(|segment| segment.strip_suffix('}'))
+                {
+                    Some(name) => Segment::Param(name.to_string()),
+                    None => Segment::Literal(segment.to_string()),
+                }
+            })
+            .collect();
+        self.routes.push(Route {
+            method: method.to_uppercase(),
+            segments,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get<F>(self, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(&RouteRequest) -> HttpResult + 'static,
+    {
+        self.route("GET", pattern, handler)
+    }
+
+    pub fn post<F>(self, pattern: &str, handler: F) -> Self
+    where
+        F: Fn(&RouteRequest) -> HttpResult + 'static,
+    {
+        self.route("POST", pattern, handler)
+    }
+
+    /// Adds a hook run before routing, returning a response skips the route, e.g. to reject
+    /// unauthenticated requests.
+    pub fn before<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Option<HttpResponse> + 'static,
+    {
+        self.before.push(Box::new(hook));
+        self
+    }
+
+    /// Adds a hook run on every response, including 404 and 405 responses.
+    pub fn after<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&HttpRequest, &mut HttpResponse) + 'static,
+    {
+        self.after.push(Box::new(hook));
+        self
+    }
+
+    /// Error responses built while handling `request`, including those of the routes, are
+    /// localized to its `Accept-Language`, see [request_locale].
+    pub fn handle(&self, request: &HttpRequest) -> HttpResponse {
+        with_request_locale(request_locale(request), || {
+            let mut response = self
+                .before
+                .iter()
+                .find_map(|hook| hook(request))
+                .unwrap_or_else(|| self.dispatch(request).unwrap_or_else(|response| response));
+            for hook in self.after.iter() {
+                hook(request, &mut response);
+            }
+            response
+        })
+    }
+
+    fn dispatch(&self, request: &HttpRequest) -> HttpResult {
+        let url = request.parse_url()?;
+        let path: Vec<String> = split_path(url.path())
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().to_string())
+            .collect();
+        let method = request.method.to_uppercase();
+        let mut allowed: Vec<&str> = vec![];
+        for route in self.routes.iter() {
+            let params = match match_segments(&route.segments, &path) {
+                Some(params) => params,
+                None => continue,
+            };
+            if route.method != method {
+                if !allowed.contains(&route.method.as_str()) {
+                    allowed.push(&route.method);
+                }
+                continue;
+            }
+            return (route.handler)(&RouteRequest {
+                request,
+                url,
+                params,
+            });
+        }
+
+        if allowed.is_empty() {
+            return Err(CommonError::HttpRouteNotFound {
+                path: url.path().to_string(),
+            }
+            .into());
+        }
+        let mut response: HttpResponse = CommonError::HttpMethodNotAllowed {
+            method: request.method.clone(),
+            path: url.path().to_string(),
+        }
+        .into();
+        response
+            .headers
+            .push(HeaderField("Allow".to_string(), allowed.join(", ")));
+        Err(response)
+    }
+}
+
+fn split_path(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn match_segments(segments: &[Segment], path: &[String]) -> Option<BTreeMap<String, String>> {
+    if segments.len() != path.len() {
+        return None;
+    }
+    let mut params = BTreeMap::new();
+    for (segment, value) in segments.iter().zip(path) {
+        match segment {
+            Segment::Literal(literal) if literal == value => {}
+            Segment::Literal(_) => return None,
+            Segment::Param(name) => {
+                params.insert(name.clone(), value.clone());
+            }
+        }
+    }
+    Some(params)
+}
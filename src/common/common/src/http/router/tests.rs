@@ -0,0 +1,154 @@
+use rstest::*;
+
+use super::*;
+use crate::errors::i18n::{current_request_locale, register_messages};
+use crate::test_common::test::init_test;
+
+fn request(method: &str, url: &str) -> HttpRequest {
+    HttpRequest {
+        method: method.to_string(),
+        url: url.to_string(),
+        headers: vec![],
+        body: vec![],
+    }
+}
+
+fn body(response: &HttpResponse) -> String {
+    String::from_utf8(response.body.to_vec()).unwrap()
+}
+
+fn header<'a>(response: &'a HttpResponse, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|header| header.0 == name)
+        .map(|header| header.1.as_str())
+}
+
+#[fixture]
+fn router() -> Router {
+    init_test();
+    Router::new()
+        .get("/names", |_| Ok(HttpResponse::string(200, "names")))
+        .get("/names/{name}", |req| {
+            Ok(HttpResponse::string(
+                200,
+                &format!("get {}", req.param("name").unwrap()),
+            ))
+        })
+        .post("/names/{name}", |req| {
+            if req.param("name") == Some("admin") {
+                return Err(CommonError::PermissionDenied.into());
+            }
+            Ok(HttpResponse::string(201, "created"))
+        })
+        .get("/names/{name}/records/{key}", |req| {
+            Ok(HttpResponse::string(
+                200,
+                &format!(
+                    "{} {} {}",
+                    req.param("name").unwrap(),
+                    req.param("key").unwrap(),
+                    req.query("format").unwrap_or_default()
+                ),
+            ))
+        })
+}
+
+#[rstest]
+#[case("GET", "/names", 200, "names")]
+#[case("GET", "/names/", 200, "names")]
+#[case("get", "/names/hello.icp", 200, "get hello.icp")]
+#[case("GET", "/names/%E4%BD%A0%E5%A5%BD?x=1", 200, "get 你好")]
+#[case("POST", "/names/hello", 201, "created")]
+#[case("GET", "/names/hello/records/eth?format=json", 200, "hello eth json")]
+fn test_dispatch(
+    router: Router,
+    #[case] method: &str,
+    #[case] url: &str,
+    #[case] status: u16,
+    #[case] expected: &str,
+) {
+    let response = router.handle(&request(method, url));
+    assert_eq!(response.status_code, status);
+    assert_eq!(body(&response), expected);
+}
+
+#[rstest]
+fn test_handler_error(router: Router) {
+    let response = router.handle(&request("POST", "/names/admin"));
+    assert_eq!(response.status_code, 403);
+}
+
+#[rstest]
+fn test_not_found(router: Router) {
+    let response = router.handle(&request("GET", "/unknown"));
+    assert_eq!(response.status_code, 404);
+    assert!(body(&response).contains("\"code\":14"));
+}
+
+#[rstest]
+fn test_malformed_url(router: Router) {
+    let response = router.handle(&request("GET", "//["));
+    assert_eq!(response.status_code, 400);
+    assert!(body(&response).contains("\"code\":17"));
+}
+
+#[rstest]
+fn test_method_not_allowed(router: Router) {
+    let response = router.handle(&request("DELETE", "/names/hello"));
+    assert_eq!(response.status_code, 405);
+    assert_eq!(header(&response, "Allow"), Some("GET, POST"));
+}
+
+#[rstest]
+fn test_middleware_hooks(router: Router) {
+    let router = router
+        .before(|req| {
+            if req.method == "PUT" {
+                Some(HttpResponse::string(418, "rejected"))
+            } else {
+                None
+            }
+        })
+        .after(|_, response| {
+            response.headers.push(HeaderField(
+                "X-Powered-By".to_string(),
+                "router".to_string(),
+            ))
+        });
+
+    let response = router.handle(&request("PUT", "/names"));
+    assert_eq!(response.status_code, 418);
+    assert_eq!(header(&response, "X-Powered-By"), Some("router"));
+
+    let response = router.handle(&request("GET", "/unknown"));
+    assert_eq!(response.status_code, 404);
+    assert_eq!(header(&response, "X-Powered-By"), Some("router"));
+}
+
+#[rstest]
+#[case("/names/admin", "POST", 403, "没有权限")]
+#[case("/unknown", "GET", 404, "找不到 /unknown")]
+fn test_problem_responses_are_localized(
+    router: Router,
+    #[case] url: &str,
+    #[case] method: &str,
+    #[case] status: u16,
+    #[case] message: &str,
+) {
+    register_messages("zh", &[(4, "没有权限"), (14, "找不到 {path}")]);
+    let mut localized = request(method, url);
+    localized.headers.push(HeaderField(
+        "Accept-Language".to_string(),
+        "zh-CN,zh;q=0.9".to_string(),
+    ));
+
+    let response = router.handle(&localized);
+    assert_eq!(response.status_code, status);
+    assert!(body(&response).contains(&format!("\"message\":\"{}\"", message)));
+    assert_eq!(current_request_locale(), None);
+
+    let response = router.handle(&request(method, url));
+    assert!(!body(&response).contains(message));
+}
@@ -0,0 +1,206 @@
+use std::cell::RefCell;
+use std::ops::Range;
+
+use candid::{Func, Nat};
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+use crate::constants::HTTP_STREAMING_CHUNK_SIZE;
+use crate::errors::{CommonError, ServiceResult};
+use crate::http::{
+    CallbackStrategy, HttpResponse, StreamingCallbackHttpResponse, StreamingStrategy, Token,
+};
+
+#[cfg(test)]
+mod tests;
+
+pub const HTTP_STREAMING_CALLBACK_METHOD: &str = "http_request_streaming_callback";
+
+const IDENTITY_ENCODING: &str = "identity";
+
+type BodyProvider = Box<dyn Fn(&str, Range<usize>) -> Option<BodyRange>>;
+
+thread_local! {
+    static STREAMING_BODIES: RefCell<Vec<(String, BodyProvider)>> = RefCell::new(vec![]);
+}
+
+/// Bytes of a range of a streamed body, with the sha256 of the whole body they are taken from.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BodyRange {
+    pub bytes: Vec<u8>,
+    pub sha256: [u8; 32],
+}
+
+/// Registers the provider of streamed bodies whose key starts with `prefix`, the longest matching
+/// prefix is used.
+///
+/// `http_request` is a query, so nothing it builds is kept for the callback: the provider renders
+/// the bytes of `range` of the current body of a key for every chunk, clamped to the end of the
+/// body, and `None` for unknown keys. Rendering only the range keeps streaming a body linear in
+/// its size, so the provider should return the hash of the whole body without hashing it, e.g. one
+/// kept with the data it renders.
+///
+/// A chunk is only returned if the hash matches the hash in the token of the first response, so a
+/// body changed between chunks fails the stream instead of mixing old and new bytes.
+///
+/// ```ignore
+/// register_streaming_body("/exports/", |key, range| {
+///     let export = load_export(key)?;
+///     Some(BodyRange {
+///         bytes: render_rows(&export.rows, range),
+///         sha256: export.sha256,
+///     })
+/// });
+/// ```
+pub fn register_streaming_body<F>(prefix: &str, provider: F)
+where
+    F: Fn(&str, Range<usize>) -> Option<BodyRange> + 'static,
+{
+    STREAMING_BODIES.with(|bodies| {
+        let mut bodies = bodies.borrow_mut();
+        bodies.retain(|(existing, _)| existing != prefix);
+        bodies.push((prefix.to_string(), Box::new(provider)));
+    });
+}
+
+/// Bytes of `range` of `body`, clamped to its end, for providers holding the whole body.
+///
+/// The whole body is hashed for every chunk, providers of large bodies should keep its hash and
+/// build the [BodyRange] themselves.
+pub fn body_range(body: &[u8], range: Range<usize>) -> BodyRange {
+    let end = range.end.min(body.len());
+    BodyRange {
+        bytes: body[range.start.min(end)..end].to_vec(),
+        sha256: Sha256::digest(body).into(),
+    }
+}
+
+fn render_range(key: &str, range: Range<usize>) -> Option<BodyRange> {
+    STREAMING_BODIES.with(|bodies| {
+        bodies
+            .borrow()
+            .iter()
+            .filter(|(prefix, _)| key.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .and_then(|(_, provider)| provider(key, range))
+    })
+}
+
+impl Token {
+    /// Token of chunk `index` of `body`, streamed under `key`.
+    pub fn new(key: &str, index: u64, body: &[u8]) -> Self {
+        Self {
+            key: key.to_string(),
+            content_encoding: IDENTITY_ENCODING.to_string(),
+            index: Nat::from(index),
+            sha256: Some(ByteBuf::from(Sha256::digest(body).to_vec())),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Hash of the whole streamed body.
+    pub fn sha256(&self) -> Option<[u8; 32]> {
+        self.sha256
+            .as_ref()
+            .and_then(|sha256| sha256.as_slice().try_into().ok())
+    }
+}
+
+/// The callback of the current canister, see [http_request_streaming_callback].
+pub fn streaming_callback() -> Func {
+    Func {
+        principal: ic_cdk::id(),
+        method: HTTP_STREAMING_CALLBACK_METHOD.to_string(),
+    }
+}
+
+impl HttpResponse {
+    /// Returns `body` in one response if it fits in a chunk, otherwise its first chunk with a
+    /// callback strategy for the rest. `key` must render the same body in the provider registered
+    /// with [register_streaming_body].
+    pub fn streamed(status_code: u16, key: &str, body: Vec<u8>, callback: Func) -> HttpResponse {
+        if body.len() <= HTTP_STREAMING_CHUNK_SIZE {
+            return HttpResponse::new(status_code, body);
+        }
+        let token = Token::new(key, 1, &body);
+        let mut response =
+            HttpResponse::new(status_code, body[..HTTP_STREAMING_CHUNK_SIZE].to_vec());
+        response.streaming_strategy = Some(StreamingStrategy::Callback(CallbackStrategy {
+            callback,
+            token,
+        }));
+        response
+    }
+}
+
+/// Returns the chunk requested by `token`, with the token of the next chunk if any.
+///
+/// `render_range` returns the bytes of a range of the body, see [register_streaming_body]. It is
+/// asked for one byte past the chunk to know whether another chunk follows. The chunk is rejected
+/// if the body changed since the token was issued.
+pub fn stream_chunk<F>(
+    token: &Token,
+    render_range: F,
+) -> ServiceResult<StreamingCallbackHttpResponse>
+where
+    F: FnOnce(Range<usize>) -> Option<BodyRange>,
+{
+    let invalid = |detail: &str| CommonError::InvalidStreamingToken {
+        detail: detail.to_string(),
+    };
+    let index = u64::try_from(&token.index.0).map_err(|_| invalid("index is too large"))?;
+    let start = usize::try_from(index)
+        .ok()
+        .and_then(|index| index.checked_mul(HTTP_STREAMING_CHUNK_SIZE))
+        .filter(|_| index > 0)
+        .ok_or_else(|| invalid("index is out of range"))?;
+    let expected_sha256 = token
+        .sha256()
+        .ok_or_else(|| invalid("token has no body hash"))?;
+    let end = start.saturating_add(HTTP_STREAMING_CHUNK_SIZE + 1);
+    let range = render_range(start..end)
+        .ok_or_else(|| invalid(&format!("no body for key {}", token.key)))?;
+    if range.sha256 != expected_sha256 {
+        return Err(invalid(&format!(
+            "body of key {} changed since the first chunk",
+            token.key
+        )));
+    }
+    let mut body = range.bytes;
+    if body.is_empty() {
+        return Err(invalid("index is out of range"));
+    }
+    let has_next = body.len() > HTTP_STREAMING_CHUNK_SIZE;
+    body.truncate(HTTP_STREAMING_CHUNK_SIZE);
+    Ok(StreamingCallbackHttpResponse {
+        body,
+        token: has_next.then(|| Token {
+            index: Nat::from(index + 1),
+            ..token.clone()
+        }),
+    })
+}
+
+/// Handler of the `http_request_streaming_callback` query.
+///
+/// A chunk can not carry a status, so an invalid token ends the stream with the problem document
+/// of the error as the last chunk, like the router answers bad requests. The assembled body then
+/// no longer matches the hash of a certified response and is rejected by the boundary nodes.
+///
+/// ```ignore
+/// #[query]
+/// fn http_request_streaming_callback(token: Token) -> StreamingCallbackHttpResponse {
+///     common::http::streaming::http_request_streaming_callback(token)
+/// }
+/// ```
+pub fn http_request_streaming_callback(token: Token) -> StreamingCallbackHttpResponse {
+    stream_chunk(&token, |range| render_range(&token.key, range)).unwrap_or_else(|err| {
+        StreamingCallbackHttpResponse {
+            body: HttpResponse::from(err).body.into_vec(),
+            token: None,
+        }
+    })
+}
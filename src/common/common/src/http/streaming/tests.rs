@@ -0,0 +1,163 @@
+use candid::Principal;
+use rstest::*;
+
+use super::*;
+use crate::test_common::test::init_test;
+
+const KEY: &str = "/exports/names.csv";
+
+fn large_body() -> Vec<u8> {
+    (0..HTTP_STREAMING_CHUNK_SIZE * 2 + 10)
+        .map(|i| (i % 251) as u8)
+        .collect()
+}
+
+fn callback() -> Func {
+    Func {
+        principal: Principal::anonymous(),
+        method: HTTP_STREAMING_CALLBACK_METHOD.to_string(),
+    }
+}
+
+fn first_token(response: &HttpResponse) -> Token {
+    match &response.streaming_strategy {
+        Some(StreamingStrategy::Callback(strategy)) => strategy.token.clone(),
+        None => panic!("response is not streamed"),
+    }
+}
+
+#[rstest]
+fn test_small_body_is_not_streamed() {
+    init_test();
+    let response = HttpResponse::streamed(200, KEY, vec![1, 2, 3], callback());
+    assert_eq!(response.body.to_vec(), vec![1, 2, 3]);
+    assert!(response.streaming_strategy.is_none());
+}
+
+fn stream(body: &[u8]) -> (Vec<u8>, usize) {
+    let response = HttpResponse::streamed(200, KEY, body.to_vec(), callback());
+    let mut streamed = response.body.to_vec();
+    let mut token = Some(first_token(&response));
+    let mut chunks = 1;
+    while let Some(current) = token {
+        let chunk = stream_chunk(&current, |range| Some(body_range(body, range))).unwrap();
+        streamed.extend(chunk.body);
+        token = chunk.token;
+        chunks += 1;
+    }
+    (streamed, chunks)
+}
+
+#[rstest]
+fn test_stream_chunks() {
+    init_test();
+    let body = large_body();
+    let (streamed, chunks) = stream(&body);
+    assert_eq!(chunks, 3);
+    assert_eq!(streamed, body);
+}
+
+#[rstest]
+fn test_stream_body_of_whole_chunks() {
+    init_test();
+    let body = vec![7u8; HTTP_STREAMING_CHUNK_SIZE * 2];
+    let (streamed, chunks) = stream(&body);
+    assert_eq!(chunks, 2);
+    assert_eq!(streamed, body);
+}
+
+#[rstest]
+fn test_stream_chunk_renders_only_its_range() {
+    init_test();
+    let body = large_body();
+    let token = first_token(&HttpResponse::streamed(200, KEY, body.clone(), callback()));
+
+    let mut rendered = vec![];
+    let chunk = stream_chunk(&token, |range| {
+        rendered.push(range.clone());
+        Some(body_range(&body, range))
+    })
+    .unwrap();
+    assert_eq!(
+        rendered,
+        vec![HTTP_STREAMING_CHUNK_SIZE..HTTP_STREAMING_CHUNK_SIZE * 2 + 1]
+    );
+    assert_eq!(chunk.body.len(), HTTP_STREAMING_CHUNK_SIZE);
+}
+
+#[rstest]
+fn test_stream_chunk_rejects_out_of_range_index() {
+    init_test();
+    let body = large_body();
+    let render = |range| Some(body_range(&body, range));
+    assert!(stream_chunk(&Token::new(KEY, 0, &body), render).is_err());
+    assert!(stream_chunk(&Token::new(KEY, 3, &body), render).is_err());
+    assert!(stream_chunk(&Token::new(KEY, 2, &body), render).is_ok());
+    assert!(stream_chunk(&Token::new(KEY, 1, &body), |_| None).is_err());
+}
+
+#[rstest]
+fn test_stream_chunk_rejects_changed_body() {
+    init_test();
+    let body = large_body();
+    let token = first_token(&HttpResponse::streamed(200, KEY, body.clone(), callback()));
+
+    let mut changed = body.clone();
+    changed[0] += 1;
+    let result = stream_chunk(&token, |range| Some(body_range(&changed, range)));
+    assert!(matches!(
+        result,
+        Err(CommonError::InvalidStreamingToken { .. })
+    ));
+
+    let token = Token {
+        sha256: None,
+        ..token
+    };
+    assert!(stream_chunk(&token, |range| Some(body_range(&body, range))).is_err());
+}
+
+#[rstest]
+#[case(0..3, vec![1, 2, 3])]
+#[case(2..10, vec![3, 4])]
+#[case(5..10, vec![])]
+fn test_body_range(#[case] range: Range<usize>, #[case] expected: Vec<u8>) {
+    let range = body_range(&[1, 2, 3, 4], range);
+    assert_eq!(range.bytes, expected);
+    assert_eq!(
+        range.sha256,
+        <[u8; 32]>::from(Sha256::digest([1u8, 2, 3, 4]))
+    );
+}
+
+#[rstest]
+fn test_http_request_streaming_callback() {
+    init_test();
+    register_streaming_body("/exports/", |_, _| None);
+    register_streaming_body("/exports/names", |_, range| {
+        Some(body_range(&large_body(), range))
+    });
+
+    let body = large_body();
+    let token = first_token(&HttpResponse::streamed(200, KEY, body.clone(), callback()));
+    let chunk = http_request_streaming_callback(token);
+    assert_eq!(
+        chunk.body,
+        body[HTTP_STREAMING_CHUNK_SIZE..HTTP_STREAMING_CHUNK_SIZE * 2]
+    );
+    assert_eq!(chunk.token.unwrap().key(), KEY);
+}
+
+#[rstest]
+fn test_http_request_streaming_callback_returns_problem() {
+    init_test();
+    register_streaming_body("/exports/", |_, _| None);
+
+    let body = large_body();
+    let token = first_token(&HttpResponse::streamed(200, KEY, body, callback()));
+    let chunk = http_request_streaming_callback(token);
+    assert!(chunk.token.is_none());
+    let problem: serde_json::Value = serde_json::from_slice(&chunk.body).unwrap();
+    assert_eq!(problem["code"], 16);
+    assert_eq!(problem["status"], 400);
+}
@@ -61,3 +61,39 @@ mod url_parse {
         assert_eq!(values[1], "nice");
     }
 }
+
+mod request_url {
+    use super::*;
+
+    fn request(url: &str) -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    #[rstest]
+    fn test_parse_url() {
+        let url = request("/names?name=nice").parse_url().unwrap();
+        assert_eq!(url.path(), "/names");
+        assert_eq!(
+            request("/names?name=nice").get_query_value("name"),
+            Some("nice".to_string())
+        );
+    }
+
+    #[rstest]
+    #[case("//[")]
+    #[case("http://[::1")]
+    fn test_parse_malformed_url(#[case] url: &str) {
+        let request = request(url);
+        assert!(matches!(
+            request.parse_url(),
+            Err(CommonError::InvalidUrl { .. })
+        ));
+        assert_eq!(request.get_query_value("name"), None);
+        assert!(request.get_query_values("name").is_empty());
+    }
+}
@@ -7,6 +7,10 @@ use ic_cdk::api;
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, Sub};
 
+// allows `#[derive(StableState)]` to refer to `::common` inside this crate
+extern crate self as common;
+
+pub mod batch;
 pub mod constants;
 pub mod dto;
 pub mod errors;
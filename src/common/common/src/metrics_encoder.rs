@@ -1,6 +1,35 @@
 //! Encodes metrics for Prometheus.
+use std::cell::RefCell;
 use std::io;
 
+#[cfg(test)]
+mod tests;
+
+type MetricsCollector = Box<dyn Fn(&mut MetricsEncoder<Vec<u8>>) -> io::Result<()>>;
+
+thread_local! {
+    static METRICS_COLLECTORS: RefCell<Vec<MetricsCollector>> = RefCell::new(vec![]);
+}
+
+/// Registers metrics of the canister, encoded on every scrape of `/metrics` after the built-in
+/// metrics. Register collectors in `init` and `post_upgrade`.
+pub fn register_metrics<F>(collector: F)
+where
+    F: Fn(&mut MetricsEncoder<Vec<u8>>) -> io::Result<()> + 'static,
+{
+    METRICS_COLLECTORS.with(|collectors| collectors.borrow_mut().push(Box::new(collector)));
+}
+
+/// Encodes the metrics of all collectors added by [register_metrics].
+pub fn encode_registered_metrics(encoder: &mut MetricsEncoder<Vec<u8>>) -> io::Result<()> {
+    METRICS_COLLECTORS.with(|collectors| {
+        collectors
+            .borrow()
+            .iter()
+            .try_for_each(|collector| collector(encoder))
+    })
+}
+
 /// `MetricsEncoder` provides methods to encode metrics in a text format
 /// that can be understood by Prometheus.
 ///
@@ -99,4 +128,32 @@ impl<W: io::Write> MetricsEncoder<W> {
     pub fn encode_gauge(&mut self, name: &str, value: f64, help: &str) -> io::Result<()> {
         self.encode_single_value("gauge", name, value, help)
     }
+
+    /// Encodes a gauge of value 1 whose labels carry the information, e.g. build info.
+    pub fn encode_info(
+        &mut self,
+        name: &str,
+        labels: &[(&str, &str)],
+        help: &str,
+    ) -> io::Result<()> {
+        self.encode_header(name, help, "gauge")?;
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, escape_label_value(value)))
+            .collect();
+        writeln!(
+            self.writer,
+            "{}{{{}}} 1 {}",
+            name,
+            labels.join(","),
+            self.now_millis
+        )
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
@@ -0,0 +1,33 @@
+use rstest::*;
+
+use super::*;
+
+#[rstest]
+fn test_encode_info() {
+    let mut encoder = MetricsEncoder::new(vec![], 1000);
+    encoder
+        .encode_info(
+            "canister_build_info",
+            &[("version", "0.1.0"), ("branch", "feat/\"x\"")],
+            "Build information.",
+        )
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(encoder.into_inner()).unwrap(),
+        "# HELP canister_build_info Build information.\n\
+         # TYPE canister_build_info gauge\n\
+         canister_build_info{version=\"0.1.0\",branch=\"feat/\\\"x\\\"\"} 1 1000\n"
+    );
+}
+
+#[rstest]
+fn test_encode_registered_metrics() {
+    register_metrics(|encoder| encoder.encode_counter("names_total", 3.0, "Registered names."));
+    register_metrics(|encoder| encoder.encode_gauge("orders_pending", 1.0, "Pending orders."));
+
+    let mut encoder = MetricsEncoder::new(vec![], 1000);
+    encode_registered_metrics(&mut encoder).unwrap();
+    let text = String::from_utf8(encoder.into_inner()).unwrap();
+    assert!(text.contains("# TYPE names_total counter\nnames_total 3 1000\n"));
+    assert!(text.contains("# TYPE orders_pending gauge\norders_pending 1 1000\n"));
+}
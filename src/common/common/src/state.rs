@@ -1,7 +1,51 @@
+pub mod collections;
+pub mod envelope;
+pub mod migration;
+pub mod snapshots;
+pub mod stable_memory;
+pub mod transfer;
+
+use crate::state::collections::decode_with_collection_contents;
+
+pub use common_macros::StableState;
+pub use envelope::{StateDecodeError, StateEnvelope};
+pub use migration::StateMigrations;
+
 pub trait StableState: Sized {
-    fn encode(&self) -> Vec<u8>;
-    fn decode(bytes: Vec<u8>) -> Result<Self, String>;
+    /// Version of the payload layout, written into the state envelope.
+    /// Increase it when the encoded layout of the state changes.
+    const SCHEMA_VERSION: u32 = 1;
+
+    /// Migrations applied by [StableState::decode] to payloads written with an older schema version.
+    fn migrations() -> StateMigrations {
+        StateMigrations::default()
+    }
+
+    fn encode_payload(&self) -> Vec<u8>;
+    fn decode_payload(bytes: Vec<u8>) -> Result<Self, StateDecodeError>;
+
+    /// Encodes the state into a [StateEnvelope], with the entries of its stable collections.
+    fn encode(&self) -> Vec<u8> {
+        StateEnvelope::new(Self::SCHEMA_VERSION, self.encode_payload()).to_bytes()
+    }
+
+    /// Decodes a state encoded by [StableState::encode], the envelope is verified before the payload is decoded.
+    /// Payloads of an older schema version are migrated to [StableState::SCHEMA_VERSION] first.
+    ///
+    /// Stable collections encoded with their entries get these entries back, see
+    /// [collections::decode_with_collection_contents].
+    fn decode(bytes: Vec<u8>) -> Result<Self, StateDecodeError> {
+        let envelope = StateEnvelope::from_bytes(&bytes)?;
+        let payload = Self::migrations().migrate(
+            envelope.payload,
+            envelope.schema_version,
+            Self::SCHEMA_VERSION,
+        )?;
+        decode_with_collection_contents(payload, Self::decode_payload)
+    }
 }
 
+#[cfg(test)]
+mod derive_tests;
 #[cfg(test)]
 mod stable_tests;
@@ -0,0 +1,515 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+
+use candid::types::{Serializer, Type, TypeId};
+use candid::{decode_one, encode_one, CandidType, Deserialize};
+use ic_stable_structures::btreemap::BTreeMap;
+use ic_stable_structures::log::Log;
+use ic_stable_structures::vec::Vec as StableStructuresVec;
+use ic_stable_structures::{BoundedStorable, Storable};
+use serde::de::{DeserializeOwned, Error as _};
+use serde::Deserializer;
+use thiserror::Error;
+
+use crate::errors::ServiceResult;
+use crate::state::stable_memory::{managed_memory, ManagedMemory, COLLECTION_MEMORY_ID_OFFSET};
+use crate::state::StateDecodeError;
+
+#[cfg(test)]
+mod tests;
+
+pub const DEFAULT_KEY_MAX_SIZE: u32 = 256;
+pub const DEFAULT_VALUE_MAX_SIZE: u32 = 1024;
+
+pub type CollectionMemory = ManagedMemory;
+
+/// Returns the memory of collection memory id `memory_id`.
+///
+/// Every collection must use its own memory ids, they are offset by [COLLECTION_MEMORY_ID_OFFSET]
+/// so that they do not collide with the memories of the state and snapshots.
+pub fn collection_memory(memory_id: u8) -> CollectionMemory {
+    let id = memory_id
+        .checked_add(COLLECTION_MEMORY_ID_OFFSET)
+        .filter(|id| *id != u8::MAX)
+        .unwrap_or_else(|| panic!("stable collection memory id {} is too large", memory_id));
+    managed_memory(id)
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum ContentsMode {
+    /// Decoded entries are written into the memory of the collection.
+    Restore,
+    /// Decoded entries are only checked, the memory is left unchanged.
+    Validate,
+}
+
+thread_local! {
+    static INCLUDE_CONTENTS: Cell<bool> = Cell::new(true);
+    static CONTENTS_MODE: Cell<ContentsMode> = Cell::new(ContentsMode::Restore);
+    static CONTENTS_FOUND: Cell<bool> = Cell::new(false);
+}
+
+/// Runs `f` with stable collections encoding their memory ids only, used to save the state for an
+/// upgrade since the entries stay in stable memory.
+pub fn without_collection_contents<R>(f: impl FnOnce() -> R) -> R {
+    let previous = INCLUDE_CONTENTS.with(|include| include.replace(false));
+    let result = f();
+    INCLUDE_CONTENTS.with(|include| include.set(previous));
+    result
+}
+
+fn with_contents_mode<R>(mode: ContentsMode, f: impl FnOnce() -> R) -> R {
+    let previous = CONTENTS_MODE.with(|current| current.replace(mode));
+    let result = f();
+    CONTENTS_MODE.with(|current| current.set(previous));
+    result
+}
+
+/// Decodes `payload` with `decode`, collections encoded with their entries are restored only if
+/// the whole payload decodes, so that a broken state leaves the collections unchanged.
+pub fn decode_with_collection_contents<S>(
+    payload: Vec<u8>,
+    decode: impl Fn(Vec<u8>) -> Result<S, StateDecodeError>,
+) -> Result<S, StateDecodeError> {
+    CONTENTS_FOUND.with(|found| found.set(false));
+    let state = with_contents_mode(ContentsMode::Validate, || decode(payload.clone()))?;
+    if !CONTENTS_FOUND.with(|found| found.get()) {
+        return Ok(state);
+    }
+    drop(state);
+    with_contents_mode(ContentsMode::Restore, || decode(payload))
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+pub enum StableCollectionError {
+    #[error("failed to grow the stable memory of collection {memory_id:?}")]
+    GrowFailed { memory_id: u8 },
+    #[error("value of collection {memory_id:?} is {size:?} bytes, the maximum is {max_size:?}")]
+    ValueTooLarge {
+        memory_id: u8,
+        size: u64,
+        max_size: u32,
+    },
+}
+
+/// A value stored with its candid encoding.
+///
+/// `MAX_SIZE` bounds the encoded size including the candid header, collections return
+/// [StableCollectionError::ValueTooLarge] for a larger value instead of storing it.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct Candid<T, const MAX_SIZE: u32 = DEFAULT_VALUE_MAX_SIZE>(pub T);
+
+impl<T, const MAX_SIZE: u32> Candid<T, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    /// Wraps `value` if its encoding fits in `MAX_SIZE`, stable structures trap on larger values.
+    fn bounded(value: T, memory_id: u8) -> Result<Self, StableCollectionError> {
+        let candid = Candid(value);
+        let size = candid.to_bytes().len() as u64;
+        if size > MAX_SIZE as u64 {
+            return Err(StableCollectionError::ValueTooLarge {
+                memory_id,
+                size,
+                max_size: MAX_SIZE,
+            });
+        }
+        Ok(candid)
+    }
+}
+
+impl<T, const MAX_SIZE: u32> Storable for Candid<T, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Owned(encode_one(&self.0).expect("failed to encode stable value"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Candid(decode_one(&bytes).expect("failed to decode stable value"))
+    }
+}
+
+impl<T, const MAX_SIZE: u32> BoundedStorable for Candid<T, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    const MAX_SIZE: u32 = MAX_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+/// What a stable collection encodes to when it is a field of a [crate::state::StableState]: its
+/// memory id and its entries, in iteration order.
+///
+/// The entries are left out when the state is saved for an upgrade, see
+/// [without_collection_contents], so that they are not copied. Exports, snapshots and state hashes
+/// include them, and decoding a state with entries replaces the entries of the collection.
+#[derive(CandidType, Deserialize)]
+pub struct StableCollectionData<E> {
+    memory_id: u8,
+    entries: Option<Vec<E>>,
+}
+
+impl<E> StableCollectionData<E>
+where
+    E: CandidType + DeserializeOwned,
+{
+    /// Data of the collection of `memory_id` holding `entries`, for a migration moving a field of
+    /// a state into a stable collection.
+    ///
+    /// ```ignore
+    /// fn migrate_v1_to_v2(bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+    ///     migrate_args(bytes, |(names,): (BTreeMap<String, Principal>,)| {
+    ///         (StableCollectionData::with_entries(NAMES_MEMORY_ID, names.into_iter().collect()),)
+    ///     })
+    /// }
+    /// ```
+    pub fn with_entries(memory_id: u8, entries: Vec<E>) -> Self {
+        Self {
+            memory_id,
+            entries: Some(entries),
+        }
+    }
+
+    fn new(memory_id: u8, entries: impl FnOnce() -> Vec<E>) -> Self {
+        Self {
+            memory_id,
+            entries: INCLUDE_CONTENTS.with(|include| include.get()).then(entries),
+        }
+    }
+
+    /// Returns the entries to restore, `None` if the collection keeps its current entries.
+    fn deserialize_expected<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        memory_id: u8,
+    ) -> Result<Option<Vec<E>>, D::Error> {
+        let collection = StableCollectionData::<E>::deserialize(deserializer)?;
+        if collection.memory_id != memory_id {
+            return Err(D::Error::custom(format!(
+                "stable collection memory id mismatch, expected {}, actual {}",
+                memory_id, collection.memory_id
+            )));
+        }
+        let entries = match collection.entries {
+            Some(entries) => entries,
+            None => return Ok(None),
+        };
+        CONTENTS_FOUND.with(|found| found.set(true));
+        match CONTENTS_MODE.with(|mode| mode.get()) {
+            ContentsMode::Restore => Ok(Some(entries)),
+            ContentsMode::Validate => Ok(None),
+        }
+    }
+}
+
+macro_rules! stable_collection_data_type {
+    ($entry:ty) => {
+        fn id() -> TypeId {
+            StableCollectionData::<$entry>::id()
+        }
+
+        fn _ty() -> Type {
+            StableCollectionData::<$entry>::_ty()
+        }
+    };
+}
+
+/// A B-tree map of candid values kept in stable memory `MEMORY_ID`.
+///
+/// As a field of a `StableState` the entries are not copied on upgrade, see [StableCollectionData].
+pub struct StableMap<
+    K,
+    V,
+    const MEMORY_ID: u8,
+    const KEY_MAX_SIZE: u32 = DEFAULT_KEY_MAX_SIZE,
+    const VALUE_MAX_SIZE: u32 = DEFAULT_VALUE_MAX_SIZE,
+> where
+    K: CandidType + DeserializeOwned + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    inner: BTreeMap<Candid<K, KEY_MAX_SIZE>, Candid<V, VALUE_MAX_SIZE>, CollectionMemory>,
+}
+
+impl<K, V, const MEMORY_ID: u8, const KEY_MAX_SIZE: u32, const VALUE_MAX_SIZE: u32>
+    StableMap<K, V, MEMORY_ID, KEY_MAX_SIZE, VALUE_MAX_SIZE>
+where
+    K: CandidType + DeserializeOwned + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.inner.get(&Candid(key.clone())).map(|value| value.0)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.inner.contains_key(&Candid(key.clone()))
+    }
+
+    /// Inserts `value`, fails if the key or the value is larger than its maximum size.
+    pub fn insert(&mut self, key: K, value: V) -> ServiceResult<Option<V>> {
+        let key = Candid::<K, KEY_MAX_SIZE>::bounded(key, MEMORY_ID)?;
+        let value = Candid::<V, VALUE_MAX_SIZE>::bounded(value, MEMORY_ID)?;
+        Ok(self.inner.insert(key, value).map(|value| value.0))
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.inner.remove(&Candid(key.clone())).map(|value| value.0)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Iterates the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (K, V)> + '_ {
+        self.inner.iter().map(|(key, value)| (key.0, value.0))
+    }
+}
+
+impl<K, V, const MEMORY_ID: u8, const KEY_MAX_SIZE: u32, const VALUE_MAX_SIZE: u32> Default
+    for StableMap<K, V, MEMORY_ID, KEY_MAX_SIZE, VALUE_MAX_SIZE>
+where
+    K: CandidType + DeserializeOwned + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    fn default() -> Self {
+        Self {
+            inner: BTreeMap::init(collection_memory(MEMORY_ID)),
+        }
+    }
+}
+
+impl<K, V, const MEMORY_ID: u8, const KEY_MAX_SIZE: u32, const VALUE_MAX_SIZE: u32> CandidType
+    for StableMap<K, V, MEMORY_ID, KEY_MAX_SIZE, VALUE_MAX_SIZE>
+where
+    K: CandidType + DeserializeOwned + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    stable_collection_data_type!((K, V));
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        StableCollectionData::new(MEMORY_ID, || self.iter().collect()).idl_serialize(serializer)
+    }
+}
+
+impl<'de, K, V, const MEMORY_ID: u8, const KEY_MAX_SIZE: u32, const VALUE_MAX_SIZE: u32>
+    Deserialize<'de> for StableMap<K, V, MEMORY_ID, KEY_MAX_SIZE, VALUE_MAX_SIZE>
+where
+    K: CandidType + DeserializeOwned + Ord + Clone,
+    V: CandidType + DeserializeOwned,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = match StableCollectionData::deserialize_expected(deserializer, MEMORY_ID)? {
+            Some(entries) => entries,
+            None => return Ok(Self::default()),
+        };
+        let mut map = Self {
+            inner: BTreeMap::new(collection_memory(MEMORY_ID)),
+        };
+        for (key, value) in entries {
+            map.insert(key, value).map_err(D::Error::custom)?;
+        }
+        Ok(map)
+    }
+}
+
+/// A growable array of candid values kept in stable memory `MEMORY_ID`.
+///
+/// Encodes like [StableMap] as a field of a `StableState`.
+pub struct StableVector<T, const MEMORY_ID: u8, const MAX_SIZE: u32 = DEFAULT_VALUE_MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    inner: StableStructuresVec<Candid<T, MAX_SIZE>, CollectionMemory>,
+}
+
+impl<T, const MEMORY_ID: u8, const MAX_SIZE: u32> StableVector<T, MEMORY_ID, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    pub fn get(&self, index: u64) -> Option<T> {
+        self.inner.get(index).map(|value| value.0)
+    }
+
+    /// Replaces the item at `index`, panics if `index` is out of bounds.
+    pub fn set(&self, index: u64, item: T) -> ServiceResult<()> {
+        self.inner
+            .set(index, &Candid::<T, MAX_SIZE>::bounded(item, MEMORY_ID)?);
+        Ok(())
+    }
+
+    pub fn push(&self, item: T) -> ServiceResult<()> {
+        self.inner
+            .push(&Candid::<T, MAX_SIZE>::bounded(item, MEMORY_ID)?)
+            .map_err(|_| {
+                StableCollectionError::GrowFailed {
+                    memory_id: MEMORY_ID,
+                }
+                .into()
+            })
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.inner.pop().map(|value| value.0)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.inner.iter().map(|value| value.0)
+    }
+}
+
+impl<T, const MEMORY_ID: u8, const MAX_SIZE: u32> Default for StableVector<T, MEMORY_ID, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn default() -> Self {
+        Self {
+            inner: StableStructuresVec::init(collection_memory(MEMORY_ID))
+                .expect("failed to init stable vector"),
+        }
+    }
+}
+
+impl<T, const MEMORY_ID: u8, const MAX_SIZE: u32> CandidType
+    for StableVector<T, MEMORY_ID, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    stable_collection_data_type!(T);
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        StableCollectionData::new(MEMORY_ID, || self.iter().collect()).idl_serialize(serializer)
+    }
+}
+
+impl<'de, T, const MEMORY_ID: u8, const MAX_SIZE: u32> Deserialize<'de>
+    for StableVector<T, MEMORY_ID, MAX_SIZE>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = match StableCollectionData::deserialize_expected(deserializer, MEMORY_ID)? {
+            Some(items) => items,
+            None => return Ok(Self::default()),
+        };
+        let vector = Self {
+            inner: StableStructuresVec::new(collection_memory(MEMORY_ID)).map_err(|_| {
+                D::Error::custom(StableCollectionError::GrowFailed {
+                    memory_id: MEMORY_ID,
+                })
+            })?,
+        };
+        for item in items {
+            vector.push(item).map_err(D::Error::custom)?;
+        }
+        Ok(vector)
+    }
+}
+
+/// An append-only log of candid values of any size, kept in the stable memories
+/// `INDEX_MEMORY_ID` and `DATA_MEMORY_ID`.
+///
+/// Encodes like [StableMap] as a field of a `StableState`, with the index memory id.
+pub struct StableLog<T, const INDEX_MEMORY_ID: u8, const DATA_MEMORY_ID: u8>
+where
+    T: CandidType + DeserializeOwned,
+{
+    inner: Log<Candid<T>, CollectionMemory, CollectionMemory>,
+}
+
+impl<T, const INDEX_MEMORY_ID: u8, const DATA_MEMORY_ID: u8>
+    StableLog<T, INDEX_MEMORY_ID, DATA_MEMORY_ID>
+where
+    T: CandidType + DeserializeOwned,
+{
+    /// Appends `entry` and returns its index.
+    pub fn append(&self, entry: T) -> ServiceResult<u64> {
+        self.inner.append(&Candid(entry)).map_err(|_| {
+            StableCollectionError::GrowFailed {
+                memory_id: DATA_MEMORY_ID,
+            }
+            .into()
+        })
+    }
+
+    pub fn get(&self, index: u64) -> Option<T> {
+        self.inner.get(index).map(|entry| entry.0)
+    }
+
+    pub fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.inner.iter().map(|entry| entry.0)
+    }
+}
+
+impl<T, const INDEX_MEMORY_ID: u8, const DATA_MEMORY_ID: u8> Default
+    for StableLog<T, INDEX_MEMORY_ID, DATA_MEMORY_ID>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn default() -> Self {
+        Self {
+            inner: Log::init(
+                collection_memory(INDEX_MEMORY_ID),
+                collection_memory(DATA_MEMORY_ID),
+            )
+            .expect("failed to init stable log"),
+        }
+    }
+}
+
+impl<T, const INDEX_MEMORY_ID: u8, const DATA_MEMORY_ID: u8> CandidType
+    for StableLog<T, INDEX_MEMORY_ID, DATA_MEMORY_ID>
+where
+    T: CandidType + DeserializeOwned,
+{
+    stable_collection_data_type!(T);
+
+    fn idl_serialize<S: Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        StableCollectionData::new(INDEX_MEMORY_ID, || self.iter().collect())
+            .idl_serialize(serializer)
+    }
+}
+
+impl<'de, T, const INDEX_MEMORY_ID: u8, const DATA_MEMORY_ID: u8> Deserialize<'de>
+    for StableLog<T, INDEX_MEMORY_ID, DATA_MEMORY_ID>
+where
+    T: CandidType + DeserializeOwned,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries =
+            match StableCollectionData::deserialize_expected(deserializer, INDEX_MEMORY_ID)? {
+                Some(entries) => entries,
+                None => return Ok(Self::default()),
+            };
+        let log = Self {
+            inner: Log::new(
+                collection_memory(INDEX_MEMORY_ID),
+                collection_memory(DATA_MEMORY_ID),
+            ),
+        };
+        for entry in entries {
+            log.append(entry).map_err(D::Error::custom)?;
+        }
+        Ok(log)
+    }
+}
@@ -0,0 +1,245 @@
+use std::cell::RefCell;
+
+use candid::{decode_args, encode_args, Principal};
+use ic_stable_structures::VectorMemory;
+use rstest::*;
+
+use super::*;
+use crate::errors::CommonError;
+use crate::state::stable_memory::{read_state_bytes, save_state, STATE_MEMORY_ID};
+use crate::state::{StableState, StateDecodeError, StateEnvelope};
+use crate::test_common::test::init_test;
+
+const REGISTRIES_MEMORY_ID: u8 = 0;
+const NAMES_MEMORY_ID: u8 = 1;
+const EVENTS_INDEX_MEMORY_ID: u8 = 2;
+const EVENTS_DATA_MEMORY_ID: u8 = 3;
+
+#[derive(Default, StableState)]
+struct CollectionState {
+    counter: RefCell<u64>,
+    registries: RefCell<StableMap<String, Principal, REGISTRIES_MEMORY_ID>>,
+    names: RefCell<StableVector<String, NAMES_MEMORY_ID>>,
+    events: RefCell<StableLog<String, EVENTS_INDEX_MEMORY_ID, EVENTS_DATA_MEMORY_ID>>,
+}
+
+#[fixture]
+fn state() -> CollectionState {
+    init_test();
+    let state = CollectionState::default();
+    *state.counter.borrow_mut() = 1;
+    state
+        .registries
+        .borrow_mut()
+        .insert("b.ic".to_string(), Principal::anonymous())
+        .unwrap();
+    state
+        .registries
+        .borrow_mut()
+        .insert("a.ic".to_string(), Principal::management_canister())
+        .unwrap();
+    state.names.borrow().push("a".to_string()).unwrap();
+    state.events.borrow().append("created".to_string()).unwrap();
+    state
+}
+
+#[rstest]
+fn test_map(state: CollectionState) {
+    let mut registries = state.registries.borrow_mut();
+    assert_eq!(registries.len(), 2);
+    assert_eq!(
+        registries.get(&"b.ic".to_string()),
+        Some(Principal::anonymous())
+    );
+    assert_eq!(
+        registries.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+        vec!["a.ic".to_string(), "b.ic".to_string()]
+    );
+
+    assert_eq!(
+        registries.remove(&"b.ic".to_string()),
+        Some(Principal::anonymous())
+    );
+    assert!(!registries.contains_key(&"b.ic".to_string()));
+    assert_eq!(registries.len(), 1);
+}
+
+#[rstest]
+fn test_vector(state: CollectionState) {
+    let names = state.names.borrow();
+    names.push("b".to_string()).unwrap();
+    names.set(0, "c".to_string()).unwrap();
+    assert_eq!(
+        names.iter().collect::<Vec<_>>(),
+        vec!["c".to_string(), "b".to_string()]
+    );
+    assert_eq!(names.pop(), Some("b".to_string()));
+    assert_eq!(names.len(), 1);
+    assert_eq!(names.get(1), None);
+}
+
+#[rstest]
+fn test_value_too_large(state: CollectionState) {
+    let name = "a".repeat(DEFAULT_KEY_MAX_SIZE as usize);
+    assert!(matches!(
+        state
+            .registries
+            .borrow_mut()
+            .insert(name.clone(), Principal::anonymous()),
+        Err(CommonError::StableCollectionError(
+            StableCollectionError::ValueTooLarge {
+                memory_id: REGISTRIES_MEMORY_ID,
+                max_size: DEFAULT_KEY_MAX_SIZE,
+                ..
+            }
+        ))
+    ));
+    assert!(!state.registries.borrow().contains_key(&name));
+
+    let long_name = "a".repeat(DEFAULT_VALUE_MAX_SIZE as usize);
+    let names = state.names.borrow();
+    assert!(names.push(long_name.clone()).is_err());
+    assert!(names.set(0, long_name).is_err());
+    assert_eq!(names.iter().collect::<Vec<_>>(), vec!["a".to_string()]);
+}
+
+#[rstest]
+fn test_log(state: CollectionState) {
+    let events = state.events.borrow();
+    assert_eq!(events.append("updated".to_string()), Ok(1));
+    assert_eq!(events.get(1), Some("updated".to_string()));
+    assert_eq!(events.len(), 2);
+}
+
+#[rstest]
+fn test_state_encodes_entries(state: CollectionState) {
+    let (counter, registries, names, events): (
+        u64,
+        StableCollectionData<(String, Principal)>,
+        StableCollectionData<String>,
+        StableCollectionData<String>,
+    ) = decode_args(&state.encode_payload()).unwrap();
+    assert_eq!(counter, 1);
+    assert_eq!(registries.memory_id, REGISTRIES_MEMORY_ID);
+    assert_eq!(
+        registries.entries,
+        Some(vec![
+            ("a.ic".to_string(), Principal::management_canister()),
+            ("b.ic".to_string(), Principal::anonymous()),
+        ])
+    );
+    assert_eq!(names.memory_id, NAMES_MEMORY_ID);
+    assert_eq!(names.entries, Some(vec!["a".to_string()]));
+    assert_eq!(events.memory_id, EVENTS_INDEX_MEMORY_ID);
+    assert_eq!(events.entries, Some(vec!["created".to_string()]));
+}
+
+#[rstest]
+fn test_upgrade_encodes_memory_ids_only(state: CollectionState) {
+    let memory = VectorMemory::default();
+    save_state(memory.clone(), &state).unwrap();
+    let bytes = read_state_bytes(memory).unwrap().unwrap();
+    let envelope = StateEnvelope::from_bytes(&bytes).unwrap();
+    let (_, registries, names, events): (
+        u64,
+        StableCollectionData<(String, Principal)>,
+        StableCollectionData<String>,
+        StableCollectionData<String>,
+    ) = decode_args(&envelope.payload).unwrap();
+    assert_eq!(registries.entries, None);
+    assert_eq!(names.entries, None);
+    assert_eq!(events.entries, None);
+
+    // the entries stay in the memories of the collections
+    let restored = CollectionState::decode(bytes).unwrap();
+    assert_eq!(restored.registries.borrow().len(), 2);
+    assert_eq!(restored.names.borrow().get(0), Some("a".to_string()));
+    assert_eq!(restored.events.borrow().get(0), Some("created".to_string()));
+}
+
+#[rstest]
+fn test_decode_restores_entries(state: CollectionState) {
+    let exported = state.encode();
+    state
+        .registries
+        .borrow_mut()
+        .insert("c.ic".to_string(), Principal::anonymous())
+        .unwrap();
+    state.names.borrow().push("b".to_string()).unwrap();
+    state.events.borrow().append("updated".to_string()).unwrap();
+
+    let decoded = CollectionState::decode(exported.clone()).unwrap();
+    assert_eq!(*decoded.counter.borrow(), 1);
+    assert_eq!(
+        decoded.registries.borrow().iter().collect::<Vec<_>>(),
+        vec![
+            ("a.ic".to_string(), Principal::management_canister()),
+            ("b.ic".to_string(), Principal::anonymous()),
+        ]
+    );
+    assert_eq!(
+        decoded.names.borrow().iter().collect::<Vec<_>>(),
+        vec!["a".to_string()]
+    );
+    assert_eq!(
+        decoded.events.borrow().iter().collect::<Vec<_>>(),
+        vec!["created".to_string()]
+    );
+    assert_eq!(decoded.encode(), exported);
+}
+
+#[rstest]
+fn test_failed_decode_keeps_entries(state: CollectionState) {
+    let bytes = encode_args((
+        1u64,
+        StableCollectionData {
+            memory_id: REGISTRIES_MEMORY_ID,
+            entries: Some(Vec::<(String, Principal)>::new()),
+        },
+        StableCollectionData {
+            memory_id: NAMES_MEMORY_ID,
+            entries: Some(Vec::<String>::new()),
+        },
+        "not a collection".to_string(),
+    ))
+    .unwrap();
+    let result = CollectionState::decode(StateEnvelope::new(1, bytes).to_bytes());
+    assert!(matches!(
+        result,
+        Err(StateDecodeError::PayloadDecodeError { .. })
+    ));
+    assert_eq!(state.registries.borrow().len(), 2);
+    assert_eq!(state.names.borrow().len(), 1);
+}
+
+#[rstest]
+fn test_decode_memory_id_mismatch() {
+    init_test();
+    let bytes = encode_args((
+        1u64,
+        StableCollectionData::<(String, Principal)> {
+            memory_id: 9,
+            entries: None,
+        },
+        StableCollectionData::<String> {
+            memory_id: NAMES_MEMORY_ID,
+            entries: None,
+        },
+        StableCollectionData::<String> {
+            memory_id: EVENTS_INDEX_MEMORY_ID,
+            entries: None,
+        },
+    ))
+    .unwrap();
+    let result = CollectionState::decode_payload(bytes);
+    assert!(matches!(
+        result,
+        Err(StateDecodeError::PayloadDecodeError { .. })
+    ));
+}
+
+#[rstest]
+fn test_collections_do_not_touch_state_memory(state: CollectionState) {
+    assert_eq!(state.registries.borrow().len(), 2);
+    assert_eq!(read_state_bytes(managed_memory(STATE_MEMORY_ID)), Ok(None));
+}
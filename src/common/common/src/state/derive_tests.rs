@@ -0,0 +1,149 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use candid::{decode_args, encode_args, Principal};
+use rstest::*;
+
+use crate::state::{StableState, StateDecodeError, StateEnvelope, StateMigrations};
+use crate::test_common::test::init_test;
+
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+struct StateV1 {
+    counter: RefCell<u64>,
+    owners: RefCell<BTreeMap<String, Principal>>,
+}
+
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+struct StateV2 {
+    counter: RefCell<u64>,
+    owners: RefCell<BTreeMap<String, Principal>>,
+    #[stable(since = 2, default = 100)]
+    limit: RefCell<u32>,
+    #[stable(since = 2)]
+    names: RefCell<Vec<String>>,
+    #[stable(skip)]
+    cache: RefCell<HashMap<String, u64>>,
+}
+
+/// Same layout as [StateV2], declared with the `stable_state` alias.
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+#[stable_state(version = 2)]
+struct AliasState {
+    counter: RefCell<u64>,
+    owners: RefCell<BTreeMap<String, Principal>>,
+    #[stable_state(since = 2, default = 100)]
+    limit: RefCell<u32>,
+}
+
+fn rename_counter(bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+    let (counter,): (u64,) = decode_args(&bytes)?;
+    Ok(encode_args((counter + 1,))?)
+}
+
+fn counter_migrations() -> StateMigrations {
+    StateMigrations::new().register(1, rename_counter)
+}
+
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+#[stable(version = 2, migrations = counter_migrations)]
+struct MigratedState {
+    counter: RefCell<u64>,
+}
+
+#[fixture]
+fn state_v1() -> StateV1 {
+    init_test();
+    let mut owners = BTreeMap::new();
+    owners.insert(
+        "test".to_string(),
+        Principal::from_str("zo36k-iqaaa-aaaaj-qahdq-cai").unwrap(),
+    );
+    StateV1 {
+        counter: RefCell::new(12),
+        owners: RefCell::new(owners),
+    }
+}
+
+#[rstest]
+fn test_schema_version() {
+    assert_eq!(StateV1::SCHEMA_VERSION, 1);
+    assert_eq!(StateV2::SCHEMA_VERSION, 2);
+    assert_eq!(MigratedState::SCHEMA_VERSION, 2);
+}
+
+#[rstest]
+fn test_stable_state_alias(state_v1: StateV1) {
+    assert_eq!(AliasState::SCHEMA_VERSION, 2);
+    let decoded = AliasState::decode(state_v1.encode()).unwrap();
+    assert_eq!(*decoded.counter.borrow(), 12);
+    assert_eq!(*decoded.limit.borrow(), 100);
+}
+
+#[rstest]
+fn test_encode_decode(state_v1: StateV1) {
+    let decoded = StateV1::decode(state_v1.encode()).unwrap();
+    assert_eq!(decoded, state_v1);
+}
+
+#[rstest]
+fn test_payload_is_field_tuple(state_v1: StateV1) {
+    let (counter, owners): (u64, BTreeMap<String, Principal>) =
+        decode_args(&state_v1.encode_payload()).unwrap();
+    assert_eq!(counter, 12);
+    assert_eq!(owners, *state_v1.owners.borrow());
+}
+
+#[rstest]
+fn test_decode_older_state_with_defaults(state_v1: StateV1) {
+    let decoded = StateV2::decode(state_v1.encode()).unwrap();
+    assert_eq!(*decoded.counter.borrow(), 12);
+    assert_eq!(*decoded.owners.borrow(), *state_v1.owners.borrow());
+    assert_eq!(*decoded.limit.borrow(), 100);
+    assert!(decoded.names.borrow().is_empty());
+}
+
+#[rstest]
+fn test_skipped_field_is_not_persisted(state_v1: StateV1) {
+    let state = StateV2 {
+        counter: state_v1.counter,
+        owners: state_v1.owners,
+        limit: RefCell::new(7),
+        names: RefCell::new(vec!["a".to_string()]),
+        cache: RefCell::new(HashMap::from([("a".to_string(), 1)])),
+    };
+    let decoded = StateV2::decode(state.encode()).unwrap();
+    assert_eq!(*decoded.limit.borrow(), 7);
+    assert_eq!(*decoded.names.borrow(), vec!["a".to_string()]);
+    assert!(decoded.cache.borrow().is_empty());
+}
+
+#[rstest]
+fn test_decode_pre_envelope_state(state_v1: StateV1) {
+    let legacy = encode_args((12u64, &*state_v1.owners.borrow())).unwrap();
+    let decoded = StateV1::decode(legacy).unwrap();
+    assert_eq!(decoded, state_v1);
+}
+
+#[rstest]
+fn test_pre_envelope_state_is_migrated(state_v1: StateV1) {
+    let legacy = encode_args((12u64, &*state_v1.owners.borrow())).unwrap();
+    let decoded = StateV2::decode(legacy).unwrap();
+    assert_eq!(*decoded.counter.borrow(), 12);
+    assert_eq!(*decoded.limit.borrow(), 100);
+}
+
+#[rstest]
+fn test_migrations_are_applied() {
+    init_test();
+    let bytes = StateEnvelope::new(1, encode_args((1u64,)).unwrap()).to_bytes();
+    let decoded = MigratedState::decode(bytes).unwrap();
+    assert_eq!(*decoded.counter.borrow(), 2);
+}
+
+#[rstest]
+fn test_migrations_are_applied_to_pre_envelope_state() {
+    init_test();
+    let decoded = MigratedState::decode(encode_args((1u64,)).unwrap()).unwrap();
+    assert_eq!(*decoded.counter.borrow(), 2);
+}
@@ -0,0 +1,110 @@
+use candid::{CandidType, Deserialize};
+use thiserror::Error;
+
+#[cfg(test)]
+mod tests;
+
+/// Magic number at the start of every encoded state.
+pub const STATE_MAGIC: [u8; 4] = *b"CTST";
+
+/// magic (4) + schema version (4) + payload length (8) + crc32 of payload (4)
+pub const STATE_HEADER_SIZE: usize = 20;
+
+/// Schema version of states encoded before the envelope was introduced, as a plain candid payload.
+/// Register a migration from this version if the legacy payload differs from version 1.
+pub const LEGACY_SCHEMA_VERSION: u32 = 0;
+
+/// Magic number at the start of every candid message, legacy states start with it.
+const CANDID_MAGIC: [u8; 4] = *b"DIDL";
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+pub enum StateDecodeError {
+    #[error("state data is too short, got {length:?} bytes")]
+    TooShort { length: u64 },
+    #[error("invalid state magic number {magic:?}")]
+    InvalidMagic { magic: Vec<u8> },
+    #[error("state length mismatch, expected {expected:?} bytes, actual {actual:?}")]
+    LengthMismatch { expected: u64, actual: u64 },
+    #[error("state checksum mismatch, expected {expected:?}, actual {actual:?}")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+    #[error("unsupported state schema version {version:?}, current version is {current:?}")]
+    UnsupportedVersion { version: u32, current: u32 },
+    #[error("failed to decode state payload, detail: {detail:?}")]
+    PayloadDecodeError { detail: String },
+    #[error("failed to migrate state from version {from_version:?}, detail: {detail:?}")]
+    MigrationFailed { from_version: u32, detail: String },
+}
+
+impl From<candid::Error> for StateDecodeError {
+    fn from(error: candid::Error) -> Self {
+        StateDecodeError::PayloadDecodeError {
+            detail: error.to_string(),
+        }
+    }
+}
+
+/// Framed state: a header with magic, schema version, payload length and crc32 checksum,
+/// followed by the payload. All numbers are little endian.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StateEnvelope {
+    pub schema_version: u32,
+    pub payload: Vec<u8>,
+}
+
+impl StateEnvelope {
+    pub fn new(schema_version: u32, payload: Vec<u8>) -> Self {
+        Self {
+            schema_version,
+            payload,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(STATE_HEADER_SIZE + self.payload.len());
+        bytes.extend_from_slice(&STATE_MAGIC);
+        bytes.extend_from_slice(&self.schema_version.to_le_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&crc32fast::hash(&self.payload).to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    /// Verifies and decodes the envelope of `bytes`. A plain candid payload without an envelope is
+    /// returned as a state of the [LEGACY_SCHEMA_VERSION], its checksum can not be verified.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateDecodeError> {
+        if bytes.starts_with(&CANDID_MAGIC) {
+            return Ok(Self::new(LEGACY_SCHEMA_VERSION, bytes.to_vec()));
+        }
+        if bytes.len() < STATE_HEADER_SIZE {
+            return Err(StateDecodeError::TooShort {
+                length: bytes.len() as u64,
+            });
+        }
+        let (header, payload) = bytes.split_at(STATE_HEADER_SIZE);
+        if header[0..4] != STATE_MAGIC {
+            return Err(StateDecodeError::InvalidMagic {
+                magic: header[0..4].to_vec(),
+            });
+        }
+        let schema_version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let length = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        if length != payload.len() as u64 {
+            return Err(StateDecodeError::LengthMismatch {
+                expected: length,
+                actual: payload.len() as u64,
+            });
+        }
+        let checksum = u32::from_le_bytes(header[16..20].try_into().unwrap());
+        let actual_checksum = crc32fast::hash(payload);
+        if checksum != actual_checksum {
+            return Err(StateDecodeError::ChecksumMismatch {
+                expected: checksum,
+                actual: actual_checksum,
+            });
+        }
+        Ok(Self {
+            schema_version,
+            payload: payload.to_vec(),
+        })
+    }
+}
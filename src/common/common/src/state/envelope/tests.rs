@@ -0,0 +1,81 @@
+use rstest::*;
+
+use super::*;
+
+#[fixture]
+fn envelope_bytes() -> Vec<u8> {
+    StateEnvelope::new(3, vec![1, 2, 3, 4, 5]).to_bytes()
+}
+
+#[rstest]
+fn test_encode_decode(envelope_bytes: Vec<u8>) {
+    assert_eq!(envelope_bytes.len(), STATE_HEADER_SIZE + 5);
+    assert_eq!(envelope_bytes[0..4], STATE_MAGIC);
+
+    let envelope = StateEnvelope::from_bytes(&envelope_bytes).unwrap();
+    assert_eq!(envelope, StateEnvelope::new(3, vec![1, 2, 3, 4, 5]));
+}
+
+#[rstest]
+fn test_encode_decode_empty_payload() {
+    let bytes = StateEnvelope::new(1, vec![]).to_bytes();
+    let envelope = StateEnvelope::from_bytes(&bytes).unwrap();
+    assert_eq!(envelope, StateEnvelope::new(1, vec![]));
+}
+
+#[rstest]
+fn test_decode_too_short(envelope_bytes: Vec<u8>) {
+    assert_eq!(
+        StateEnvelope::from_bytes(&envelope_bytes[0..10]),
+        Err(StateDecodeError::TooShort { length: 10 })
+    );
+}
+
+#[rstest]
+fn test_decode_invalid_magic(mut envelope_bytes: Vec<u8>) {
+    envelope_bytes[0..4].copy_from_slice(b"XXXX");
+    assert_eq!(
+        StateEnvelope::from_bytes(&envelope_bytes),
+        Err(StateDecodeError::InvalidMagic {
+            magic: b"XXXX".to_vec()
+        })
+    );
+}
+
+#[rstest]
+fn test_decode_legacy_state_without_envelope() {
+    let legacy = candid::encode_args((1u64, "name".to_string())).unwrap();
+    let envelope = StateEnvelope::from_bytes(&legacy).unwrap();
+    assert_eq!(envelope, StateEnvelope::new(LEGACY_SCHEMA_VERSION, legacy));
+}
+
+#[rstest]
+fn test_decode_legacy_empty_state() {
+    let legacy = candid::encode_args(()).unwrap();
+    assert!(legacy.len() < STATE_HEADER_SIZE);
+    let envelope = StateEnvelope::from_bytes(&legacy).unwrap();
+    assert_eq!(envelope, StateEnvelope::new(LEGACY_SCHEMA_VERSION, legacy));
+}
+
+#[rstest]
+fn test_decode_truncated_payload(envelope_bytes: Vec<u8>) {
+    let length = envelope_bytes.len();
+    assert_eq!(
+        StateEnvelope::from_bytes(&envelope_bytes[0..length - 1]),
+        Err(StateDecodeError::LengthMismatch {
+            expected: 5,
+            actual: 4,
+        })
+    );
+}
+
+#[rstest]
+fn test_decode_corrupted_payload(mut envelope_bytes: Vec<u8>) {
+    let last = envelope_bytes.len() - 1;
+    envelope_bytes[last] ^= 0xff;
+    let result = StateEnvelope::from_bytes(&envelope_bytes);
+    assert!(matches!(
+        result,
+        Err(StateDecodeError::ChecksumMismatch { .. })
+    ));
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use candid::utils::{ArgumentDecoder, ArgumentEncoder};
+use candid::{decode_args, encode_args};
+use log::info;
+
+use crate::state::StateDecodeError;
+
+#[cfg(test)]
+mod tests;
+
+/// Transforms a payload of schema version `N` into a payload of schema version `N + 1`.
+pub type MigrationFn = fn(Vec<u8>) -> Result<Vec<u8>, StateDecodeError>;
+
+/// Ordered chain of payload migrations, keyed by the version they migrate from.
+///
+/// Versions without a registered migration are passed through unchanged, since candid already
+/// tolerates additive changes such as new optional fields.
+#[derive(Default)]
+pub struct StateMigrations {
+    steps: BTreeMap<u32, MigrationFn>,
+}
+
+impl StateMigrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the migration from `from_version` to `from_version + 1`.
+    pub fn register(mut self, from_version: u32, migrate: MigrationFn) -> Self {
+        let existing = self.steps.insert(from_version, migrate);
+        assert!(
+            existing.is_none(),
+            "migration from version {} is registered twice",
+            from_version
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Applies the registered migrations in order to bring `payload` from `from_version` to `to_version`.
+    pub fn migrate(
+        &self,
+        payload: Vec<u8>,
+        from_version: u32,
+        to_version: u32,
+    ) -> Result<Vec<u8>, StateDecodeError> {
+        if from_version > to_version {
+            return Err(StateDecodeError::UnsupportedVersion {
+                version: from_version,
+                current: to_version,
+            });
+        }
+        let mut payload = payload;
+        for (version, migrate) in self.steps.range(from_version..to_version) {
+            info!(
+                "migrating state from version {} to {}",
+                version,
+                version + 1
+            );
+            payload = migrate(payload).map_err(|err| StateDecodeError::MigrationFailed {
+                from_version: *version,
+                detail: err.to_string(),
+            })?;
+        }
+        Ok(payload)
+    }
+}
+
+/// Helper for the common migration shape: decode the old tuple, transform it and encode the new tuple.
+///
+/// ```ignore
+/// fn migrate_v1_to_v2(bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+///     migrate_args(bytes, |(count, name): (u32, String)| (count, name, 0u64))
+/// }
+/// ```
+pub fn migrate_args<Old, New, F>(bytes: Vec<u8>, migrate: F) -> Result<Vec<u8>, StateDecodeError>
+where
+    Old: for<'a> ArgumentDecoder<'a>,
+    New: ArgumentEncoder,
+    F: FnOnce(Old) -> New,
+{
+    let old: Old = decode_args(&bytes)?;
+    Ok(encode_args(migrate(old))?)
+}
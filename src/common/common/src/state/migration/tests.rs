@@ -0,0 +1,184 @@
+use std::cell::RefCell;
+
+use candid::{decode_args, encode_args, CandidType, Deserialize};
+use rstest::*;
+
+use super::*;
+use crate::state::{StableState, StateEnvelope};
+use crate::test_common::test::init_test;
+
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone)]
+struct SettingsV1 {
+    limit: u8,
+}
+
+#[derive(CandidType, Deserialize, Eq, PartialEq, Debug, Clone)]
+struct SettingsV2 {
+    limit: u8,
+    end_at: u64,
+}
+
+/// v1: (counter, settings)
+/// v2: settings.end_at is required
+/// v3: (settings, counter, name)
+#[derive(Debug, Eq, PartialEq)]
+struct TestState {
+    settings: RefCell<SettingsV2>,
+    counter: RefCell<u32>,
+    name: RefCell<String>,
+}
+
+fn migrate_v1_to_v2(bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+    migrate_args(bytes, |(counter, settings): (u32, SettingsV1)| {
+        (
+            counter,
+            SettingsV2 {
+                limit: settings.limit,
+                end_at: 100,
+            },
+        )
+    })
+}
+
+fn migrate_v2_to_v3(bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+    migrate_args(bytes, |(counter, settings): (u32, SettingsV2)| {
+        (settings, counter, "migrated".to_string())
+    })
+}
+
+fn failing_migration(_bytes: Vec<u8>) -> Result<Vec<u8>, StateDecodeError> {
+    Err(StateDecodeError::PayloadDecodeError {
+        detail: "broken".to_string(),
+    })
+}
+
+impl StableState for TestState {
+    const SCHEMA_VERSION: u32 = 3;
+
+    fn migrations() -> StateMigrations {
+        StateMigrations::new()
+            .register(1, migrate_v1_to_v2)
+            .register(2, migrate_v2_to_v3)
+    }
+
+    fn encode_payload(&self) -> Vec<u8> {
+        encode_args((
+            &*self.settings.borrow(),
+            &*self.counter.borrow(),
+            &*self.name.borrow(),
+        ))
+        .unwrap()
+    }
+
+    fn decode_payload(bytes: Vec<u8>) -> Result<Self, StateDecodeError> {
+        let (settings, counter, name): (SettingsV2, u32, String) = decode_args(&bytes)?;
+        Ok(TestState {
+            settings: RefCell::new(settings),
+            counter: RefCell::new(counter),
+            name: RefCell::new(name),
+        })
+    }
+}
+
+#[fixture]
+fn state_v1_bytes() -> Vec<u8> {
+    init_test();
+    let payload = encode_args((7u32, SettingsV1 { limit: 12 })).unwrap();
+    StateEnvelope::new(1, payload).to_bytes()
+}
+
+#[rstest]
+fn test_decode_current_version() {
+    init_test();
+    let state = TestState {
+        settings: RefCell::new(SettingsV2 {
+            limit: 1,
+            end_at: 2,
+        }),
+        counter: RefCell::new(3),
+        name: RefCell::new("current".to_string()),
+    };
+    let decoded = TestState::decode(state.encode()).unwrap();
+    assert_eq!(decoded, state);
+}
+
+#[rstest]
+fn test_decode_applies_migrations_in_order(state_v1_bytes: Vec<u8>) {
+    let decoded = TestState::decode(state_v1_bytes).unwrap();
+    assert_eq!(
+        decoded,
+        TestState {
+            settings: RefCell::new(SettingsV2 {
+                limit: 12,
+                end_at: 100,
+            }),
+            counter: RefCell::new(7),
+            name: RefCell::new("migrated".to_string()),
+        }
+    );
+}
+
+#[rstest]
+fn test_decode_rejects_newer_version() {
+    init_test();
+    let bytes = StateEnvelope::new(4, vec![]).to_bytes();
+    assert_eq!(
+        TestState::decode(bytes),
+        Err(StateDecodeError::UnsupportedVersion {
+            version: 4,
+            current: 3,
+        })
+    );
+}
+
+#[rstest]
+fn test_versions_without_migration_are_passed_through() {
+    init_test();
+    let migrations = StateMigrations::new().register(2, migrate_v2_to_v3);
+    let payload = encode_args((
+        7u32,
+        SettingsV2 {
+            limit: 1,
+            end_at: 2,
+        },
+    ))
+    .unwrap();
+
+    let migrated = migrations.migrate(payload, 1, 3).unwrap();
+
+    let (settings, counter, name): (SettingsV2, u32, String) = decode_args(&migrated).unwrap();
+    assert_eq!(
+        settings,
+        SettingsV2 {
+            limit: 1,
+            end_at: 2
+        }
+    );
+    assert_eq!(counter, 7);
+    assert_eq!(name, "migrated");
+}
+
+#[rstest]
+fn test_failed_migration_reports_version() {
+    init_test();
+    let migrations = StateMigrations::new()
+        .register(1, migrate_v1_to_v2)
+        .register(2, failing_migration);
+    let payload = encode_args((7u32, SettingsV1 { limit: 12 })).unwrap();
+
+    assert_eq!(
+        migrations.migrate(payload, 1, 3),
+        Err(StateDecodeError::MigrationFailed {
+            from_version: 2,
+            detail: "failed to decode state payload, detail: \"broken\"".to_string(),
+        })
+    );
+}
+
+#[rstest]
+#[should_panic]
+fn test_register_twice_panics() {
+    StateMigrations::new()
+        .register(1, migrate_v1_to_v2)
+        .register(1, migrate_v2_to_v3);
+}
@@ -0,0 +1,229 @@
+use std::borrow::Cow;
+
+use candid::{decode_one, encode_one, CandidType, Deserialize};
+use ic_stable_structures::btreemap::BTreeMap;
+use ic_stable_structures::{BoundedStorable, Memory, Storable};
+use thiserror::Error;
+
+use crate::constants::STATE_SNAPSHOT_MAX_COUNT;
+use crate::dto::StateSnapshotInfo;
+use crate::state::stable_memory::{
+    managed_memory, read_state_bytes, write_state_bytes, ManagedMemory, SNAPSHOT_DATA_MEMORY_ID,
+    SNAPSHOT_INDEX_MEMORY_ID,
+};
+use crate::types::TimeInNs;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+pub enum StateSnapshotError {
+    #[error("state snapshot {id:?} is not found")]
+    NotFound { id: u64 },
+    #[error("failed to access snapshot storage, detail: {detail:?}")]
+    StorageError { detail: String },
+}
+
+/// Size of the chunks the data of a snapshot is split into.
+const SNAPSHOT_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// Infos of the kept snapshots, oldest first.
+#[derive(CandidType, Deserialize, Default)]
+struct SnapshotIndex {
+    next_id: u64,
+    snapshots: Vec<StateSnapshotInfo>,
+}
+
+/// Key of chunk `index` of snapshot `snapshot_id`, chunks of a snapshot are stored in order.
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+struct ChunkKey {
+    snapshot_id: u64,
+    index: u64,
+}
+
+impl Storable for ChunkKey {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = self.snapshot_id.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.index.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self {
+            snapshot_id: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            index: u64::from_be_bytes(bytes[8..16].try_into().unwrap()),
+        }
+    }
+}
+
+impl BoundedStorable for ChunkKey {
+    const MAX_SIZE: u32 = 16;
+    const IS_FIXED_SIZE: bool = true;
+}
+
+struct SnapshotChunk(Vec<u8>);
+
+impl Storable for SnapshotChunk {
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Cow::Borrowed(&self.0)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        SnapshotChunk(bytes.into_owned())
+    }
+}
+
+impl BoundedStorable for SnapshotChunk {
+    const MAX_SIZE: u32 = SNAPSHOT_CHUNK_SIZE;
+    const IS_FIXED_SIZE: bool = false;
+}
+
+fn chunk_count(info: &StateSnapshotInfo) -> u64 {
+    (info.size + SNAPSHOT_CHUNK_SIZE as u64 - 1) / SNAPSHOT_CHUNK_SIZE as u64
+}
+
+/// Encoded states kept in their own stable memories, oldest first.
+///
+/// The index of the snapshots is kept apart from their data, which is split into chunks, so that
+/// listing the snapshots does not read their data.
+///
+/// The memories are not touched by the upgrade hooks, so snapshots survive upgrades and can be used
+/// to roll back a broken upgrade or admin operation. Only the latest `max_count` snapshots are kept.
+pub struct StateSnapshots<M: Memory> {
+    index_memory: M,
+    chunks: BTreeMap<ChunkKey, SnapshotChunk, M>,
+    max_count: usize,
+}
+
+impl Default for StateSnapshots<ManagedMemory> {
+    fn default() -> Self {
+        Self::new(
+            managed_memory(SNAPSHOT_INDEX_MEMORY_ID),
+            managed_memory(SNAPSHOT_DATA_MEMORY_ID),
+            STATE_SNAPSHOT_MAX_COUNT,
+        )
+    }
+}
+
+impl<M: Memory + Clone> StateSnapshots<M> {
+    pub fn new(index_memory: M, data_memory: M, max_count: usize) -> Self {
+        assert!(
+            max_count > 0,
+            "max_count of state snapshots must be positive"
+        );
+        Self {
+            index_memory,
+            chunks: BTreeMap::init(data_memory),
+            max_count,
+        }
+    }
+
+    fn load_index(&self) -> Result<SnapshotIndex, StateSnapshotError> {
+        let bytes = read_state_bytes(self.index_memory.clone()).map_err(|err| {
+            StateSnapshotError::StorageError {
+                detail: err.to_string(),
+            }
+        })?;
+        match bytes {
+            Some(bytes) => decode_one(&bytes).map_err(|err| StateSnapshotError::StorageError {
+                detail: err.to_string(),
+            }),
+            None => Ok(SnapshotIndex::default()),
+        }
+    }
+
+    fn store_index(&self, index: &SnapshotIndex) -> Result<(), StateSnapshotError> {
+        let bytes = encode_one(index).map_err(|err| StateSnapshotError::StorageError {
+            detail: err.to_string(),
+        })?;
+        write_state_bytes(self.index_memory.clone(), &bytes).map_err(|err| {
+            StateSnapshotError::StorageError {
+                detail: err.to_string(),
+            }
+        })
+    }
+
+    fn remove_chunks(&mut self, info: &StateSnapshotInfo) {
+        for index in 0..chunk_count(info) {
+            self.chunks.remove(&ChunkKey {
+                snapshot_id: info.id,
+                index,
+            });
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<StateSnapshotInfo>, StateSnapshotError> {
+        Ok(self.load_index()?.snapshots)
+    }
+
+    /// Keeps `data` as a new snapshot, dropping the oldest snapshots beyond `max_count`.
+    pub fn take(
+        &mut self,
+        data: Vec<u8>,
+        now: TimeInNs,
+        git_commit: &str,
+    ) -> Result<StateSnapshotInfo, StateSnapshotError> {
+        let mut index = self.load_index()?;
+        index.next_id += 1;
+        let info = StateSnapshotInfo {
+            id: index.next_id,
+            created_at: now.0,
+            git_commit: git_commit.to_string(),
+            size: data.len() as u64,
+        };
+        for (chunk_index, chunk) in data.chunks(SNAPSHOT_CHUNK_SIZE as usize).enumerate() {
+            self.chunks.insert(
+                ChunkKey {
+                    snapshot_id: info.id,
+                    index: chunk_index as u64,
+                },
+                SnapshotChunk(chunk.to_vec()),
+            );
+        }
+        index.snapshots.push(info.clone());
+        let overflow = index.snapshots.len().saturating_sub(self.max_count);
+        let dropped: Vec<StateSnapshotInfo> = index.snapshots.drain(0..overflow).collect();
+        self.store_index(&index)?;
+        for dropped in dropped.iter() {
+            self.remove_chunks(dropped);
+        }
+        Ok(info)
+    }
+
+    /// Returns the encoded state of snapshot `id`.
+    pub fn get(&self, id: u64) -> Result<Vec<u8>, StateSnapshotError> {
+        let info = self
+            .load_index()?
+            .snapshots
+            .into_iter()
+            .find(|snapshot| snapshot.id == id)
+            .ok_or(StateSnapshotError::NotFound { id })?;
+        let mut data = Vec::with_capacity(info.size as usize);
+        for index in 0..chunk_count(&info) {
+            let chunk = self
+                .chunks
+                .get(&ChunkKey {
+                    snapshot_id: id,
+                    index,
+                })
+                .ok_or_else(|| StateSnapshotError::StorageError {
+                    detail: format!("chunk {} of snapshot {} is missing", index, id),
+                })?;
+            data.extend_from_slice(&chunk.0);
+        }
+        Ok(data)
+    }
+
+    pub fn delete(&mut self, id: u64) -> Result<(), StateSnapshotError> {
+        let mut index = self.load_index()?;
+        let position = index
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.id == id)
+            .ok_or(StateSnapshotError::NotFound { id })?;
+        let info = index.snapshots.remove(position);
+        self.store_index(&index)?;
+        self.remove_chunks(&info);
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::VectorMemory;
+use rstest::*;
+
+use super::*;
+use crate::state::stable_memory::STATE_MEMORY_ID;
+use crate::test_common::test::init_test;
+
+const NOW: TimeInNs = TimeInNs(1_651_571_294_000_000_000);
+const GIT_COMMIT: &str = "0a1b2c3d";
+
+#[fixture]
+fn snapshots() -> StateSnapshots<VectorMemory> {
+    init_test();
+    StateSnapshots::new(VectorMemory::default(), VectorMemory::default(), 2)
+}
+
+#[rstest]
+fn test_take_and_get(mut snapshots: StateSnapshots<VectorMemory>) {
+    let info = snapshots.take(vec![1, 2, 3], NOW, GIT_COMMIT).unwrap();
+    assert_eq!(
+        info,
+        StateSnapshotInfo {
+            id: 1,
+            created_at: NOW.0,
+            git_commit: GIT_COMMIT.to_string(),
+            size: 3,
+        }
+    );
+    assert_eq!(snapshots.list().unwrap(), vec![info]);
+    assert_eq!(snapshots.get(1), Ok(vec![1, 2, 3]));
+}
+
+#[rstest]
+fn test_keep_latest_snapshots_only(mut snapshots: StateSnapshots<VectorMemory>) {
+    snapshots.take(vec![1], NOW, GIT_COMMIT).unwrap();
+    snapshots.take(vec![2], NOW, GIT_COMMIT).unwrap();
+    snapshots.take(vec![3], NOW, GIT_COMMIT).unwrap();
+
+    let ids: Vec<u64> = snapshots
+        .list()
+        .unwrap()
+        .iter()
+        .map(|info| info.id)
+        .collect();
+    assert_eq!(ids, vec![2, 3]);
+    assert_eq!(
+        snapshots.get(1),
+        Err(StateSnapshotError::NotFound { id: 1 })
+    );
+    // the chunks of the dropped snapshot are removed
+    assert_eq!(snapshots.chunks.len(), 2);
+}
+
+#[rstest]
+fn test_snapshot_of_several_chunks(mut snapshots: StateSnapshots<VectorMemory>) {
+    let data: Vec<u8> = (0..SNAPSHOT_CHUNK_SIZE * 2 + 10)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    let info = snapshots.take(data.clone(), NOW, GIT_COMMIT).unwrap();
+    assert_eq!(info.size, data.len() as u64);
+    assert_eq!(snapshots.chunks.len(), 3);
+    assert_eq!(snapshots.get(info.id), Ok(data));
+
+    snapshots.delete(info.id).unwrap();
+    assert_eq!(snapshots.chunks.len(), 0);
+}
+
+#[rstest]
+fn test_take_empty_snapshot(mut snapshots: StateSnapshots<VectorMemory>) {
+    let info = snapshots.take(vec![], NOW, GIT_COMMIT).unwrap();
+    assert_eq!(snapshots.get(info.id), Ok(vec![]));
+}
+
+#[rstest]
+fn test_delete(mut snapshots: StateSnapshots<VectorMemory>) {
+    snapshots.take(vec![1], NOW, GIT_COMMIT).unwrap();
+    snapshots.take(vec![2], NOW, GIT_COMMIT).unwrap();
+
+    snapshots.delete(1).unwrap();
+    assert_eq!(
+        snapshots.get(1),
+        Err(StateSnapshotError::NotFound { id: 1 })
+    );
+    assert_eq!(
+        snapshots.delete(1),
+        Err(StateSnapshotError::NotFound { id: 1 })
+    );
+
+    // ids are not reused after a delete
+    let info = snapshots.take(vec![3], NOW, GIT_COMMIT).unwrap();
+    assert_eq!(info.id, 3);
+}
+
+#[rstest]
+fn test_snapshots_do_not_touch_state_memory() {
+    init_test();
+    let manager = MemoryManager::init(VectorMemory::default());
+    let state_memory = manager.get(MemoryId::new(STATE_MEMORY_ID));
+    write_state_bytes(state_memory.clone(), &[9, 9, 9]).unwrap();
+
+    let mut snapshots = StateSnapshots::new(
+        manager.get(MemoryId::new(SNAPSHOT_INDEX_MEMORY_ID)),
+        manager.get(MemoryId::new(SNAPSHOT_DATA_MEMORY_ID)),
+        2,
+    );
+    snapshots.take(vec![1; 100_000], NOW, GIT_COMMIT).unwrap();
+
+    assert_eq!(read_state_bytes(state_memory), Ok(Some(vec![9, 9, 9])));
+}
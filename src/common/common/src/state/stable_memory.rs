@@ -0,0 +1,219 @@
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::{DefaultMemoryImpl, Memory};
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::state::collections::without_collection_contents;
+use crate::state::{StableState, StateDecodeError};
+
+#[cfg(test)]
+mod tests;
+
+pub const WASM_PAGE_SIZE_IN_BYTES: u64 = 64 * 1024;
+
+/// Size of the length prefix written in front of the encoded state.
+const STATE_LENGTH_SIZE: u64 = 8;
+
+/// Memory used to persist the canister state across upgrades.
+pub const STATE_MEMORY_ID: u8 = 0;
+/// Memories used to keep state snapshots, see [crate::state::snapshots].
+pub const SNAPSHOT_INDEX_MEMORY_ID: u8 = 1;
+pub const SNAPSHOT_DATA_MEMORY_ID: u8 = 2;
+/// Memories from this id on are used by stable collections, see
+/// [crate::state::collections::collection_memory].
+pub const COLLECTION_MEMORY_ID_OFFSET: u8 = 16;
+
+/// A memory of the memory manager sharing the stable memory.
+pub type ManagedMemory = VirtualMemory<DefaultMemoryImpl>;
+
+thread_local! {
+    static MEMORY_MANAGER: MemoryManager<DefaultMemoryImpl> =
+        MemoryManager::init(DefaultMemoryImpl::default());
+}
+
+/// Returns the memory `memory_id` of the stable memory.
+///
+/// The stable memory is shared through a memory manager, so each memory grows on its own without
+/// reserving a fixed range of pages. `255` is reserved by the memory manager.
+pub fn managed_memory(memory_id: u8) -> ManagedMemory {
+    MEMORY_MANAGER.with(|manager| manager.get(MemoryId::new(memory_id)))
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum StableStorageError {
+    #[error("failed to grow stable memory by {pages:?} pages")]
+    GrowFailed { pages: u64 },
+    #[error("stored state length {length:?} exceeds the memory capacity {capacity:?}")]
+    InvalidLength { length: u64, capacity: u64 },
+    #[error("failed to decode state, {0}")]
+    DecodeFailed(StateDecodeError),
+}
+
+/// What to do when the state found in stable memory can not be restored in `post_upgrade`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum RestoreFailurePolicy {
+    /// Trap, so that the upgrade is rolled back and the previous wasm keeps running.
+    Trap,
+    /// Log the error and keep running with the default state.
+    KeepDefault,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct StableStorageConfig {
+    pub memory_id: u8,
+    pub on_restore_failure: RestoreFailurePolicy,
+}
+
+impl Default for StableStorageConfig {
+    fn default() -> Self {
+        Self {
+            memory_id: STATE_MEMORY_ID,
+            on_restore_failure: RestoreFailurePolicy::Trap,
+        }
+    }
+}
+
+fn pages_for(bytes: u64) -> u64 {
+    (bytes + WASM_PAGE_SIZE_IN_BYTES - 1) / WASM_PAGE_SIZE_IN_BYTES
+}
+
+/// Writes `bytes` with a length prefix to the start of `memory`, growing the memory if needed.
+pub fn write_state_bytes<M: Memory>(memory: M, bytes: &[u8]) -> Result<(), StableStorageError> {
+    let required = STATE_LENGTH_SIZE + bytes.len() as u64;
+    let current_pages = memory.size();
+    let required_pages = pages_for(required);
+    if required_pages > current_pages {
+        let pages = required_pages - current_pages;
+        if memory.grow(pages) < 0 {
+            return Err(StableStorageError::GrowFailed { pages });
+        }
+    }
+    memory.write(0, &(bytes.len() as u64).to_le_bytes());
+    memory.write(STATE_LENGTH_SIZE, bytes);
+    Ok(())
+}
+
+/// Reads the bytes written by [write_state_bytes], returns `None` if nothing was written yet.
+pub fn read_state_bytes<M: Memory>(memory: M) -> Result<Option<Vec<u8>>, StableStorageError> {
+    if memory.size() == 0 {
+        return Ok(None);
+    }
+    let mut length_bytes = [0u8; STATE_LENGTH_SIZE as usize];
+    memory.read(0, &mut length_bytes);
+    let length = u64::from_le_bytes(length_bytes);
+    if length == 0 {
+        return Ok(None);
+    }
+    let capacity = memory.size() * WASM_PAGE_SIZE_IN_BYTES;
+    if length > capacity - STATE_LENGTH_SIZE {
+        return Err(StableStorageError::InvalidLength { length, capacity });
+    }
+    let mut bytes = vec![0u8; length as usize];
+    memory.read(STATE_LENGTH_SIZE, &mut bytes);
+    Ok(Some(bytes))
+}
+
+/// Saves `state` for an upgrade, stable collections keep their entries in their own memories.
+pub fn save_state<S: StableState, M: Memory>(
+    memory: M,
+    state: &S,
+) -> Result<(), StableStorageError> {
+    write_state_bytes(
+        memory,
+        without_collection_contents(|| state.encode()).as_slice(),
+    )
+}
+
+pub fn restore_state<S: StableState, M: Memory>(
+    memory: M,
+) -> Result<Option<S>, StableStorageError> {
+    match read_state_bytes(memory)? {
+        Some(bytes) => S::decode(bytes)
+            .map(Some)
+            .map_err(StableStorageError::DecodeFailed),
+        None => Ok(None),
+    }
+}
+
+/// Saves `state` into the configured memory, traps on failure so that the upgrade is aborted.
+pub fn pre_upgrade_save<S: StableState>(state: &S, config: &StableStorageConfig) {
+    match save_state(managed_memory(config.memory_id), state) {
+        Ok(_) => info!(
+            "pre_upgrade: state saved to stable memory {}",
+            config.memory_id
+        ),
+        Err(err) => {
+            let message = format!("pre_upgrade: failed to save state: {}", err);
+            error!("{}", message);
+            ic_cdk::api::trap(message.as_str());
+        }
+    }
+}
+
+/// Restores the state saved by [pre_upgrade_save].
+///
+/// Returns `None` when there is nothing to restore or the restore failed with [RestoreFailurePolicy::KeepDefault].
+pub fn post_upgrade_restore<S: StableState>(config: &StableStorageConfig) -> Option<S> {
+    match restore_state(managed_memory(config.memory_id)) {
+        Ok(Some(state)) => {
+            info!(
+                "post_upgrade: state restored from stable memory {}",
+                config.memory_id
+            );
+            Some(state)
+        }
+        Ok(None) => {
+            warn!(
+                "post_upgrade: no state found in stable memory {}",
+                config.memory_id
+            );
+            None
+        }
+        Err(err) => {
+            let message = format!("post_upgrade: failed to restore state: {}", err);
+            error!("{}", message);
+            match config.on_restore_failure {
+                RestoreFailurePolicy::Trap => ic_cdk::api::trap(message.as_str()),
+                RestoreFailurePolicy::KeepDefault => None,
+            }
+        }
+    }
+}
+
+/// Generates `pre_upgrade` and `post_upgrade` hooks which persist the given `thread_local` state
+/// in stable memory.
+///
+/// The state type must implement [StableState] and provide a `replace(&self, new_state)` method.
+///
+/// ```ignore
+/// common::stable_state_upgrade_hooks!(STATE);
+/// common::stable_state_upgrade_hooks!(STATE, StableStorageConfig::default(), canister_module_init);
+/// ```
+#[macro_export]
+macro_rules! stable_state_upgrade_hooks {
+    ($state:ident) => {
+        $crate::stable_state_upgrade_hooks!(
+            $state,
+            $crate::state::stable_memory::StableStorageConfig::default()
+        );
+    };
+    ($state:ident, $config:expr) => {
+        $crate::stable_state_upgrade_hooks!($state, $config, (|| {}));
+    };
+    ($state:ident, $config:expr, $after_restore:expr) => {
+        #[::ic_cdk_macros::pre_upgrade]
+        fn __stable_state_pre_upgrade() {
+            let config = $config;
+            $state.with(|state| $crate::state::stable_memory::pre_upgrade_save(state, &config));
+        }
+
+        #[::ic_cdk_macros::post_upgrade]
+        fn __stable_state_post_upgrade() {
+            let config = $config;
+            if let Some(restored) = $crate::state::stable_memory::post_upgrade_restore(&config) {
+                $state.with(|state| state.replace(restored));
+            }
+            ($after_restore)();
+        }
+    };
+}
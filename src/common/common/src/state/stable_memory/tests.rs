@@ -0,0 +1,123 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use candid::{decode_args, encode_args, Principal};
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager};
+use ic_stable_structures::VectorMemory;
+use rstest::*;
+
+use super::*;
+use crate::state::StateEnvelope;
+use crate::test_common::test::init_test;
+
+#[derive(Default, Debug, Eq, PartialEq)]
+struct TestState {
+    counter: RefCell<u64>,
+    owners: RefCell<BTreeMap<String, Principal>>,
+}
+
+impl StableState for TestState {
+    fn encode_payload(&self) -> Vec<u8> {
+        encode_args((&*self.counter.borrow(), &*self.owners.borrow())).unwrap()
+    }
+
+    fn decode_payload(bytes: Vec<u8>) -> Result<Self, StateDecodeError> {
+        let (counter, owners): (u64, BTreeMap<String, Principal>) = decode_args(&bytes)?;
+        Ok(TestState {
+            counter: RefCell::new(counter),
+            owners: RefCell::new(owners),
+        })
+    }
+}
+
+#[fixture]
+fn test_state() -> TestState {
+    init_test();
+    let state = TestState::default();
+    *state.counter.borrow_mut() = 42;
+    state
+        .owners
+        .borrow_mut()
+        .insert("test.ic".to_string(), Principal::anonymous());
+    state
+}
+
+#[rstest]
+fn test_save_and_restore(test_state: TestState) {
+    let memory = VectorMemory::default();
+    save_state(memory.clone(), &test_state).unwrap();
+
+    let restored: Option<TestState> = restore_state(memory).unwrap();
+    assert_eq!(restored, Some(test_state));
+}
+
+#[rstest]
+fn test_restore_from_empty_memory() {
+    init_test();
+    let memory = VectorMemory::default();
+    let restored: Option<TestState> = restore_state(memory).unwrap();
+    assert_eq!(restored, None);
+}
+
+#[rstest]
+fn test_memories_are_isolated(test_state: TestState) {
+    let manager = MemoryManager::init(VectorMemory::default());
+    let memory = manager.get(MemoryId::new(STATE_MEMORY_ID));
+    let other_memory = manager.get(MemoryId::new(SNAPSHOT_INDEX_MEMORY_ID));
+
+    save_state(other_memory.clone(), &test_state).unwrap();
+
+    let restored: Option<TestState> = restore_state(memory).unwrap();
+    assert_eq!(restored, None);
+    let restored: Option<TestState> = restore_state(other_memory).unwrap();
+    assert_eq!(restored, Some(test_state));
+}
+
+#[rstest]
+fn test_restore_fails_with_invalid_length() {
+    init_test();
+    let memory = VectorMemory::default();
+    write_state_bytes(memory.clone(), &[1, 2, 3]).unwrap();
+    memory.write(0, &u64::MAX.to_le_bytes());
+
+    assert_eq!(
+        read_state_bytes(memory),
+        Err(StableStorageError::InvalidLength {
+            length: u64::MAX,
+            capacity: WASM_PAGE_SIZE_IN_BYTES,
+        })
+    );
+}
+
+#[rstest]
+fn test_restore_fails_with_broken_state() {
+    init_test();
+    let memory = VectorMemory::default();
+    write_state_bytes(memory.clone(), &[1, 2, 3]).unwrap();
+
+    let result: Result<Option<TestState>, StableStorageError> = restore_state(memory);
+    assert_eq!(
+        result,
+        Err(StableStorageError::DecodeFailed(
+            StateDecodeError::TooShort { length: 3 }
+        ))
+    );
+}
+
+#[rstest]
+fn test_restore_fails_with_unsupported_schema_version(test_state: TestState) {
+    let memory = VectorMemory::default();
+    let envelope = StateEnvelope::new(TestState::SCHEMA_VERSION + 1, test_state.encode_payload());
+    write_state_bytes(memory.clone(), &envelope.to_bytes()).unwrap();
+
+    let result: Result<Option<TestState>, StableStorageError> = restore_state(memory);
+    assert_eq!(
+        result,
+        Err(StableStorageError::DecodeFailed(
+            StateDecodeError::UnsupportedVersion {
+                version: TestState::SCHEMA_VERSION + 1,
+                current: TestState::SCHEMA_VERSION,
+            }
+        ))
+    );
+}
@@ -0,0 +1,306 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use candid::{CandidType, Deserialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::constants::{
+    STATE_IMPORT_MAX_BUFFERED_SIZE, STATE_IMPORT_MAX_SIZE, STATE_TRANSFER_CHUNK_SIZE,
+    STATE_TRANSFER_MAX_SESSIONS, STATE_TRANSFER_SESSION_TIMEOUT_NS,
+};
+use crate::dto::{
+    BeginStateImportRequest, LoadStateRequest, StateChunk, StateExportData, StateTransferSession,
+};
+use crate::types::TimeInNs;
+
+#[cfg(test)]
+mod tests;
+
+thread_local! {
+    pub static STATE_TRANSFER_SESSIONS: RefCell<StateTransferSessions> = RefCell::new(StateTransferSessions::default());
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, CandidType, Deserialize, Error)]
+pub enum StateTransferError {
+    #[error("state transfer session {session_id:?} is not found")]
+    SessionNotFound { session_id: u64 },
+    #[error("too many state transfer sessions, max {max:?}")]
+    TooManySessions { max: u64 },
+    #[error("chunk {index:?} is out of range, chunk count is {chunk_count:?}")]
+    ChunkOutOfRange { index: u64, chunk_count: u64 },
+    #[error("sha256 of chunk {index:?} does not match")]
+    ChunkHashMismatch { index: u64 },
+    #[error("chunk {index:?} is missing")]
+    ChunkMissing { index: u64 },
+    #[error("chunk count {chunk_count:?} does not match total size {total_size:?}")]
+    InvalidChunkCount { chunk_count: u64, total_size: u64 },
+    #[error("state size mismatch, expected {expected:?} bytes, actual {actual:?}")]
+    SizeMismatch { expected: u64, actual: u64 },
+    #[error("state sha256 mismatch, expected {expected:?}, actual {actual:?}")]
+    HashMismatch { expected: String, actual: String },
+    #[error("state of {size:?} bytes exceeds the limit of {max:?} bytes")]
+    StateTooLarge { size: u64, max: u64 },
+    #[error("chunk {index:?} has {actual:?} bytes, expected {expected:?}")]
+    ChunkSizeMismatch {
+        index: u64,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("open imports can buffer {available:?} more bytes, {size:?} requested")]
+    ImportCapacityExceeded { size: u64, available: u64 },
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+fn chunk_count_for(total_size: u64) -> u64 {
+    total_size / STATE_TRANSFER_CHUNK_SIZE + u64::from(total_size % STATE_TRANSFER_CHUNK_SIZE != 0)
+}
+
+/// Size of chunk `index` of a state of `total_size` bytes, `index` must be less than the chunk count.
+fn chunk_size_at(total_size: u64, index: u64) -> u64 {
+    std::cmp::min(
+        STATE_TRANSFER_CHUNK_SIZE,
+        total_size - index * STATE_TRANSFER_CHUNK_SIZE,
+    )
+}
+
+struct ExportSession {
+    data: Vec<u8>,
+    created_at: TimeInNs,
+}
+
+struct ImportSession {
+    total_size: u64,
+    chunk_count: u64,
+    sha256: String,
+    state_sha256: Option<String>,
+    chunks: BTreeMap<u64, Vec<u8>>,
+    created_at: TimeInNs,
+}
+
+/// Sessions of chunked state exports and imports.
+///
+/// An export session keeps the full state data until it is finished or expired,
+/// an import session collects the uploaded chunks until it is finalized.
+#[derive(Default)]
+pub struct StateTransferSessions {
+    next_session_id: u64,
+    exports: HashMap<u64, ExportSession>,
+    imports: HashMap<u64, ImportSession>,
+}
+
+impl StateTransferSessions {
+    fn next_session_id(&mut self) -> u64 {
+        self.next_session_id += 1;
+        self.next_session_id
+    }
+
+    fn ensure_capacity(&mut self, now: TimeInNs) -> Result<(), StateTransferError> {
+        self.remove_expired(now);
+        if self.exports.len() + self.imports.len() >= STATE_TRANSFER_MAX_SESSIONS {
+            return Err(StateTransferError::TooManySessions {
+                max: STATE_TRANSFER_MAX_SESSIONS as u64,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn remove_expired(&mut self, now: TimeInNs) {
+        let timeout = TimeInNs(STATE_TRANSFER_SESSION_TIMEOUT_NS);
+        self.exports
+            .retain(|_, session| now - session.created_at <= timeout);
+        self.imports
+            .retain(|_, session| now - session.created_at <= timeout);
+    }
+
+    /// Starts serving `export_data` in chunks, the session describes the export like
+    /// [StateExportData] does.
+    pub fn begin_export(
+        &mut self,
+        export_data: StateExportData,
+        now: TimeInNs,
+    ) -> Result<StateTransferSession, StateTransferError> {
+        self.ensure_capacity(now)?;
+        let session_id = self.next_session_id();
+        let data = export_data.state_data;
+        let session = StateTransferSession {
+            session_id,
+            total_size: data.len() as u64,
+            chunk_size: STATE_TRANSFER_CHUNK_SIZE,
+            chunk_count: chunk_count_for(data.len() as u64),
+            sha256: sha256_hex(&data),
+            state_sha256: Some(export_data.sha256),
+            schema_version: Some(export_data.schema_version),
+            exported_at: Some(export_data.exported_at),
+            encrypted: Some(export_data.encrypted),
+        };
+        self.exports.insert(
+            session_id,
+            ExportSession {
+                data,
+                created_at: now,
+            },
+        );
+        Ok(session)
+    }
+
+    pub fn get_export_chunk(
+        &self,
+        session_id: u64,
+        index: u64,
+    ) -> Result<StateChunk, StateTransferError> {
+        let session = self
+            .exports
+            .get(&session_id)
+            .ok_or(StateTransferError::SessionNotFound { session_id })?;
+        let chunk_count = chunk_count_for(session.data.len() as u64);
+        if index >= chunk_count {
+            return Err(StateTransferError::ChunkOutOfRange { index, chunk_count });
+        }
+        let start = (index * STATE_TRANSFER_CHUNK_SIZE) as usize;
+        let end = start + chunk_size_at(session.data.len() as u64, index) as usize;
+        let data = session.data[start..end].to_vec();
+        Ok(StateChunk {
+            session_id,
+            index,
+            sha256: sha256_hex(&data),
+            data,
+        })
+    }
+
+    pub fn finish_export(&mut self, session_id: u64) -> Result<(), StateTransferError> {
+        self.exports
+            .remove(&session_id)
+            .map(|_| ())
+            .ok_or(StateTransferError::SessionNotFound { session_id })
+    }
+
+    /// Starts collecting the chunks of a state of `request.total_size` bytes.
+    ///
+    /// The chunks are kept on the heap, so the size of one import is limited to
+    /// [STATE_IMPORT_MAX_SIZE] and the declared sizes of all open imports to
+    /// [STATE_IMPORT_MAX_BUFFERED_SIZE].
+    pub fn begin_import(
+        &mut self,
+        request: BeginStateImportRequest,
+        now: TimeInNs,
+    ) -> Result<StateTransferSession, StateTransferError> {
+        if request.total_size > STATE_IMPORT_MAX_SIZE {
+            return Err(StateTransferError::StateTooLarge {
+                size: request.total_size,
+                max: STATE_IMPORT_MAX_SIZE,
+            });
+        }
+        if request.chunk_count != chunk_count_for(request.total_size) {
+            return Err(StateTransferError::InvalidChunkCount {
+                chunk_count: request.chunk_count,
+                total_size: request.total_size,
+            });
+        }
+        self.ensure_capacity(now)?;
+        let buffered: u64 = self
+            .imports
+            .values()
+            .map(|session| session.total_size)
+            .sum();
+        let available = STATE_IMPORT_MAX_BUFFERED_SIZE.saturating_sub(buffered);
+        if request.total_size > available {
+            return Err(StateTransferError::ImportCapacityExceeded {
+                size: request.total_size,
+                available,
+            });
+        }
+        let session_id = self.next_session_id();
+        self.imports.insert(
+            session_id,
+            ImportSession {
+                total_size: request.total_size,
+                chunk_count: request.chunk_count,
+                sha256: request.sha256.clone(),
+                state_sha256: request.state_sha256.clone(),
+                chunks: BTreeMap::new(),
+                created_at: now,
+            },
+        );
+        Ok(StateTransferSession {
+            session_id,
+            total_size: request.total_size,
+            chunk_size: STATE_TRANSFER_CHUNK_SIZE,
+            chunk_count: request.chunk_count,
+            sha256: request.sha256,
+            state_sha256: request.state_sha256,
+            schema_version: None,
+            exported_at: None,
+            encrypted: None,
+        })
+    }
+
+    pub fn upload_chunk(&mut self, chunk: StateChunk) -> Result<(), StateTransferError> {
+        let session =
+            self.imports
+                .get_mut(&chunk.session_id)
+                .ok_or(StateTransferError::SessionNotFound {
+                    session_id: chunk.session_id,
+                })?;
+        if chunk.index >= session.chunk_count {
+            return Err(StateTransferError::ChunkOutOfRange {
+                index: chunk.index,
+                chunk_count: session.chunk_count,
+            });
+        }
+        let expected = chunk_size_at(session.total_size, chunk.index);
+        if chunk.data.len() as u64 != expected {
+            return Err(StateTransferError::ChunkSizeMismatch {
+                index: chunk.index,
+                expected,
+                actual: chunk.data.len() as u64,
+            });
+        }
+        if sha256_hex(&chunk.data) != chunk.sha256 {
+            return Err(StateTransferError::ChunkHashMismatch { index: chunk.index });
+        }
+        session.chunks.insert(chunk.index, chunk.data);
+        Ok(())
+    }
+
+    /// Assembles the uploaded chunks and verifies size and sha256 of the whole state data.
+    ///
+    /// The session is removed whether or not the verification succeeds.
+    pub fn finalize_import(
+        &mut self,
+        session_id: u64,
+    ) -> Result<LoadStateRequest, StateTransferError> {
+        let session = self
+            .imports
+            .remove(&session_id)
+            .ok_or(StateTransferError::SessionNotFound { session_id })?;
+        let mut data = Vec::with_capacity(session.chunks.values().map(Vec::len).sum());
+        for index in 0..session.chunk_count {
+            let chunk = session
+                .chunks
+                .get(&index)
+                .ok_or(StateTransferError::ChunkMissing { index })?;
+            data.extend_from_slice(chunk);
+        }
+        if data.len() as u64 != session.total_size {
+            return Err(StateTransferError::SizeMismatch {
+                expected: session.total_size,
+                actual: data.len() as u64,
+            });
+        }
+        let sha256 = sha256_hex(&data);
+        if sha256 != session.sha256 {
+            return Err(StateTransferError::HashMismatch {
+                expected: session.sha256,
+                actual: sha256,
+            });
+        }
+        Ok(LoadStateRequest {
+            state_data: data,
+            sha256: session.state_sha256,
+        })
+    }
+}
@@ -0,0 +1,340 @@
+use rstest::*;
+
+use super::*;
+use crate::test_common::test::init_test;
+
+const NOW: TimeInNs = TimeInNs(1_651_571_294_000_000_000);
+
+#[fixture]
+fn state_data() -> Vec<u8> {
+    init_test();
+    (0..STATE_TRANSFER_CHUNK_SIZE * 2 + 10)
+        .map(|i| (i % 251) as u8)
+        .collect()
+}
+
+fn export_data(state_data: &[u8]) -> StateExportData {
+    StateExportData {
+        state_data: state_data.to_vec(),
+        sha256: sha256_hex(b"uncompressed state"),
+        schema_version: 2,
+        exported_at: NOW.0,
+        encrypted: false,
+        encrypted_sha256: None,
+    }
+}
+
+fn export_chunks(
+    sessions: &StateTransferSessions,
+    session: &StateTransferSession,
+) -> Vec<StateChunk> {
+    (0..session.chunk_count)
+        .map(|index| {
+            sessions
+                .get_export_chunk(session.session_id, index)
+                .unwrap()
+        })
+        .collect()
+}
+
+mod export {
+    use super::*;
+
+    #[rstest]
+    fn test_export_in_chunks(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let session = sessions
+            .begin_export(export_data(&state_data), NOW)
+            .unwrap();
+        assert_eq!(session.total_size, state_data.len() as u64);
+        assert_eq!(session.chunk_count, 3);
+        assert_eq!(session.sha256, sha256_hex(&state_data));
+        assert_eq!(
+            session.state_sha256,
+            Some(sha256_hex(b"uncompressed state"))
+        );
+        assert_eq!(session.schema_version, Some(2));
+        assert_eq!(session.exported_at, Some(NOW.0));
+        assert_eq!(session.encrypted, Some(false));
+
+        let chunks = export_chunks(&sessions, &session);
+        assert_eq!(chunks[0].data.len() as u64, STATE_TRANSFER_CHUNK_SIZE);
+        assert_eq!(chunks[2].data.len(), 10);
+        for chunk in chunks.iter() {
+            assert_eq!(chunk.sha256, sha256_hex(&chunk.data));
+        }
+        let data: Vec<u8> = chunks.into_iter().flat_map(|chunk| chunk.data).collect();
+        assert_eq!(data, state_data);
+
+        sessions.finish_export(session.session_id).unwrap();
+        assert_eq!(
+            sessions.get_export_chunk(session.session_id, 0),
+            Err(StateTransferError::SessionNotFound {
+                session_id: session.session_id
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_export_chunk_out_of_range(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let session = sessions
+            .begin_export(export_data(&state_data), NOW)
+            .unwrap();
+        assert_eq!(
+            sessions.get_export_chunk(session.session_id, 3),
+            Err(StateTransferError::ChunkOutOfRange {
+                index: 3,
+                chunk_count: 3,
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_sessions_are_limited_and_expire(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        for _ in 0..STATE_TRANSFER_MAX_SESSIONS {
+            sessions
+                .begin_export(export_data(&state_data), NOW)
+                .unwrap();
+        }
+        assert_eq!(
+            sessions.begin_export(export_data(&state_data), NOW).err(),
+            Some(StateTransferError::TooManySessions {
+                max: STATE_TRANSFER_MAX_SESSIONS as u64,
+            })
+        );
+
+        let later = NOW + TimeInNs(STATE_TRANSFER_SESSION_TIMEOUT_NS + 1);
+        assert!(sessions
+            .begin_export(export_data(&state_data), later)
+            .is_ok());
+    }
+}
+
+mod import {
+    use super::*;
+
+    fn begin_import(sessions: &mut StateTransferSessions, data: &[u8]) -> StateTransferSession {
+        sessions
+            .begin_import(
+                BeginStateImportRequest {
+                    total_size: data.len() as u64,
+                    chunk_count: chunk_count_for(data.len() as u64),
+                    sha256: sha256_hex(data),
+                    state_sha256: Some(sha256_hex(b"uncompressed state")),
+                },
+                NOW,
+            )
+            .unwrap()
+    }
+
+    #[rstest]
+    fn test_import_in_chunks(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let export_session = sessions
+            .begin_export(export_data(&state_data), NOW)
+            .unwrap();
+        let chunks = export_chunks(&sessions, &export_session);
+
+        let session = begin_import(&mut sessions, &state_data);
+        // chunks may arrive in any order
+        for chunk in chunks.into_iter().rev() {
+            sessions
+                .upload_chunk(StateChunk {
+                    session_id: session.session_id,
+                    ..chunk
+                })
+                .unwrap();
+        }
+
+        assert_eq!(
+            sessions.finalize_import(session.session_id),
+            Ok(LoadStateRequest {
+                state_data,
+                sha256: Some(sha256_hex(b"uncompressed state")),
+            })
+        );
+        assert_eq!(
+            sessions.finalize_import(session.session_id),
+            Err(StateTransferError::SessionNotFound {
+                session_id: session.session_id
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_import_rejects_invalid_chunk_count(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let result = sessions.begin_import(
+            BeginStateImportRequest {
+                total_size: state_data.len() as u64,
+                chunk_count: 1,
+                sha256: sha256_hex(&state_data),
+                state_sha256: None,
+            },
+            NOW,
+        );
+        assert_eq!(
+            result,
+            Err(StateTransferError::InvalidChunkCount {
+                chunk_count: 1,
+                total_size: state_data.len() as u64,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(STATE_IMPORT_MAX_SIZE + 1)]
+    #[case(u64::MAX)]
+    fn test_import_rejects_too_large_state(#[case] total_size: u64) {
+        init_test();
+        let mut sessions = StateTransferSessions::default();
+        let result = sessions.begin_import(
+            BeginStateImportRequest {
+                total_size,
+                chunk_count: chunk_count_for(total_size),
+                sha256: sha256_hex(&[]),
+                state_sha256: None,
+            },
+            NOW,
+        );
+        assert_eq!(
+            result,
+            Err(StateTransferError::StateTooLarge {
+                size: total_size,
+                max: STATE_IMPORT_MAX_SIZE,
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_import_buffer_is_limited() {
+        init_test();
+        let request = |total_size: u64| BeginStateImportRequest {
+            total_size,
+            chunk_count: chunk_count_for(total_size),
+            sha256: sha256_hex(&[]),
+            state_sha256: None,
+        };
+        let mut sessions = StateTransferSessions::default();
+        let imports = STATE_IMPORT_MAX_BUFFERED_SIZE / STATE_IMPORT_MAX_SIZE;
+        for _ in 0..imports {
+            sessions
+                .begin_import(request(STATE_IMPORT_MAX_SIZE), NOW)
+                .unwrap();
+        }
+        assert_eq!(
+            sessions.begin_import(request(1), NOW),
+            Err(StateTransferError::ImportCapacityExceeded {
+                size: 1,
+                available: STATE_IMPORT_MAX_BUFFERED_SIZE - imports * STATE_IMPORT_MAX_SIZE,
+            })
+        );
+
+        let later = NOW + TimeInNs(STATE_TRANSFER_SESSION_TIMEOUT_NS + 1);
+        assert!(sessions.begin_import(request(1), later).is_ok());
+    }
+
+    #[rstest]
+    fn test_chunk_count_does_not_overflow() {
+        assert_eq!(
+            chunk_count_for(u64::MAX),
+            u64::MAX / STATE_TRANSFER_CHUNK_SIZE + 1
+        );
+        assert_eq!(chunk_count_for(0), 0);
+        assert_eq!(chunk_count_for(STATE_TRANSFER_CHUNK_SIZE), 1);
+    }
+
+    #[rstest]
+    #[case(0, vec![1, 2, 3])]
+    #[case(2, vec![1; 11])]
+    fn test_import_rejects_chunk_with_wrong_size(
+        state_data: Vec<u8>,
+        #[case] index: u64,
+        #[case] data: Vec<u8>,
+    ) {
+        let mut sessions = StateTransferSessions::default();
+        let session = begin_import(&mut sessions, &state_data);
+        let result = sessions.upload_chunk(StateChunk {
+            session_id: session.session_id,
+            index,
+            sha256: sha256_hex(&data),
+            data: data.clone(),
+        });
+        assert_eq!(
+            result,
+            Err(StateTransferError::ChunkSizeMismatch {
+                index,
+                expected: chunk_size_at(state_data.len() as u64, index),
+                actual: data.len() as u64,
+            })
+        );
+    }
+
+    #[rstest]
+    fn test_import_rejects_chunk_with_wrong_hash(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let session = begin_import(&mut sessions, &state_data);
+        let result = sessions.upload_chunk(StateChunk {
+            session_id: session.session_id,
+            index: 2,
+            data: vec![1; 10],
+            sha256: sha256_hex(&[3, 2, 1]),
+        });
+        assert_eq!(
+            result,
+            Err(StateTransferError::ChunkHashMismatch { index: 2 })
+        );
+    }
+
+    #[rstest]
+    fn test_finalize_rejects_missing_chunk(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let session = begin_import(&mut sessions, &state_data);
+        let data = state_data[0..STATE_TRANSFER_CHUNK_SIZE as usize].to_vec();
+        sessions
+            .upload_chunk(StateChunk {
+                session_id: session.session_id,
+                index: 0,
+                sha256: sha256_hex(&data),
+                data,
+            })
+            .unwrap();
+        assert_eq!(
+            sessions.finalize_import(session.session_id),
+            Err(StateTransferError::ChunkMissing { index: 1 })
+        );
+    }
+
+    #[rstest]
+    fn test_finalize_rejects_wrong_state_hash(state_data: Vec<u8>) {
+        let mut sessions = StateTransferSessions::default();
+        let session = sessions
+            .begin_import(
+                BeginStateImportRequest {
+                    total_size: 3,
+                    chunk_count: 1,
+                    sha256: sha256_hex(&state_data),
+                    state_sha256: None,
+                },
+                NOW,
+            )
+            .unwrap();
+        sessions
+            .upload_chunk(StateChunk {
+                session_id: session.session_id,
+                index: 0,
+                data: vec![1, 2, 3],
+                sha256: sha256_hex(&[1, 2, 3]),
+            })
+            .unwrap();
+        assert_eq!(
+            sessions.finalize_import(session.session_id),
+            Err(StateTransferError::HashMismatch {
+                expected: sha256_hex(&state_data),
+                actual: sha256_hex(&[1, 2, 3]),
+            })
+        );
+    }
+}
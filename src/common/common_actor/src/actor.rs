@@ -1,32 +1,63 @@
 use std::collections::HashMap;
 
-use candid::candid_method;
+use candid::{candid_method, Principal};
 use ic_cdk::api;
 use ic_cdk_macros::*;
 use log::{debug, error, info};
 
-use common::constants::is_dev_env;
+use common::canister_api::ic_impl::ICManagementAPI;
+use common::canister_api::IICManagementAPI;
+use common::constants::{configured_state_exporter_public_key, is_dev_env};
 use common::dto::{
-    from_state_export_data, to_state_export_data, GetStatsResponse, LoadStateRequest,
-    StateExportResponse,
+    encrypt_state_export_data, from_state_export_data, parse_state_exporter_public_key,
+    to_state_export_data, BeginStateImportRequest, CompressionAlgorithm, LoadStateRequest,
+    StateChunk, StateChunkResponse, StateExportData, StateExportResponse, StateHash,
+    StateHashResponse, StateSnapshotListResponse, StateSnapshotResponse,
+    StateTransferSessionResponse,
 };
-use common::errors::{BooleanActorResponse, CommonError, ErrorInfo};
+use common::errors::{
+    merge_error_catalogs, BooleanActorResponse, CommonError, ErrorCode, ErrorCodeEntry,
+    ServiceResult,
+};
+use common::http::{HttpRequest, HttpResponse, StreamingCallbackHttpResponse, Token};
 use common::named_principals::PRINCIPAL_NAME_STATE_EXPORTER;
 use common::permissions::{must_be_named_principal, must_be_system_owner};
+use common::state::snapshots::StateSnapshots;
+use common::state::stable_memory::StableStorageConfig;
+use common::state::transfer::{sha256_hex, STATE_TRANSFER_SESSIONS};
 use common::state::StableState;
+use common::types::TimeInNs;
 
+use crate::router::{certify_routes, handle_http_request};
 use crate::state::{State, STATE};
-use crate::stats_service::{Stats, StatsService};
+use crate::stats_service::{GetStatsResponse, StatsService};
+
+common::stable_state_upgrade_hooks!(STATE, StableStorageConfig::default(), after_restore);
+
+#[init]
+fn init() {
+    certify_routes();
+}
+
+fn after_restore() {
+    certify_routes();
+}
 
 #[query(name = "get_stats")]
 #[candid_method(query, rename = "get_stats")]
-pub fn get_stats() -> GetStatsResponse<Stats> {
+pub fn get_stats() -> GetStatsResponse {
     let now = api::time();
     let service = StatsService::default();
     let stats = service.get_stats(now);
     GetStatsResponse::new(Ok(stats))
 }
 
+#[query(name = "get_error_codes")]
+#[candid_method(query, rename = "get_error_codes")]
+pub fn get_error_codes() -> Vec<ErrorCodeEntry> {
+    merge_error_catalogs(vec![CommonError::catalog()])
+}
+
 #[update(name = "export_state")]
 #[candid_method(update, rename = "export_state")]
 pub async fn export_state() -> StateExportResponse {
@@ -36,44 +67,314 @@ pub async fn export_state() -> StateExportResponse {
         return StateExportResponse::new(Err(permission_result.err().unwrap()));
     }
 
-    let source_data = STATE.with(|state| to_state_export_data(state.encode()));
-    StateExportResponse::new(Ok(source_data))
+    StateExportResponse::new(export_state_data().await)
 }
 
-#[update(name = "load_state")]
-#[candid_method(update, rename = "load_state")]
-pub fn load_state(request: LoadStateRequest) -> BooleanActorResponse {
+/// Compresses the current state and encrypts it to the public key of the state exporter when one is configured.
+async fn export_state_data() -> ServiceResult<StateExportData> {
+    let export_data = STATE.with(|state| {
+        to_state_export_data(
+            state.encode(),
+            State::SCHEMA_VERSION,
+            TimeInNs(api::time()),
+            CompressionAlgorithm::default(),
+        )
+    })?;
+    match state_exporter_public_key()? {
+        Some(public_key) => {
+            let ephemeral_secret: [u8; 32] = ICManagementAPI::default()
+                .raw_rand()
+                .await?
+                .try_into()
+                .map_err(|_| CommonError::Unknown {
+                    detail: "raw_rand returned an unexpected number of bytes".to_string(),
+                })?;
+            encrypt_state_export_data(export_data, &public_key, ephemeral_secret)
+        }
+        None => Ok(export_data),
+    }
+}
+
+/// The key set with `set_state_exporter_public_key`, or the one configured at build time.
+fn state_exporter_public_key() -> ServiceResult<Option<[u8; 32]>> {
+    let key = STATE.with(|state| state.state_exporter_public_key.borrow().clone());
+    match key {
+        Some(key) => parse_state_exporter_public_key(&key).map(Some),
+        None => configured_state_exporter_public_key(),
+    }
+}
+
+/// Sets the public key state exports are encrypted to, `None` falls back to the key configured at
+/// build time. Only the owner can register the key of the state exporter.
+#[update(name = "set_state_exporter_public_key")]
+#[candid_method(update, rename = "set_state_exporter_public_key")]
+pub fn set_state_exporter_public_key(public_key: Option<String>) -> BooleanActorResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_system_owner(caller) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    if let Some(key) = &public_key {
+        if let Err(err) = parse_state_exporter_public_key(key) {
+            return BooleanActorResponse::new(Err(err));
+        }
+    }
+    info!(
+        "set_state_exporter_public_key: {}",
+        public_key.as_deref().unwrap_or("none")
+    );
+    STATE.with(|state| state.state_exporter_public_key.replace(public_key));
+    BooleanActorResponse::new(Ok(true))
+}
+
+/// Hash of the current state, to confirm that a state loaded by `load_state` matches an export.
+///
+/// The hash covers the entries of stable collections. The encoding is canonical because the
+/// `StableState` derive only accepts ordered maps and sets, the hash of a reloaded state equals the
+/// hash of the exported one.
+#[query(name = "get_state_hash")]
+#[candid_method(query, rename = "get_state_hash")]
+pub fn get_state_hash() -> StateHashResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_named_principal(caller, PRINCIPAL_NAME_STATE_EXPORTER) {
+        return StateHashResponse::new(Err(err));
+    }
+    let sha256 = STATE.with(|state| sha256_hex(state.encode().as_slice()));
+    StateHashResponse::new(Ok(StateHash {
+        sha256,
+        schema_version: State::SCHEMA_VERSION,
+    }))
+}
+
+fn must_be_state_loader(caller: &Principal) -> ServiceResult<()> {
     if !is_dev_env() {
-        return BooleanActorResponse::new(Err(CommonError::Unknown {
+        return Err(CommonError::Unknown {
             detail: "!is_dev_env()".to_string(),
-        }));
+        });
     }
+    if must_be_system_owner(caller).is_err() {
+        error!("caller is not system owner");
+        return Err(CommonError::PermissionDenied);
+    }
+    Ok(())
+}
+
+fn replace_state(bytes: Vec<u8>) -> ServiceResult<bool> {
+    let new_state = State::decode(bytes).map_err(|err| {
+        error!("failed to decode state: {}", err);
+        CommonError::StateDecodeError(err)
+    })?;
+    STATE.with(|s| s.replace(new_state));
+    Ok(true)
+}
+
+#[update(name = "load_state")]
+#[candid_method(update, rename = "load_state")]
+pub fn load_state(request: LoadStateRequest) -> BooleanActorResponse {
     debug!("load_state: {}", request);
     let caller = &api::caller();
-    if must_be_system_owner(caller).is_err() {
-        error!("load_state: caller is not system owner");
-        return BooleanActorResponse::new(Err(CommonError::PermissionDenied));
-    }
-    STATE.with(|s| {
-        let bytes = from_state_export_data(request);
-        let result = State::decode(bytes);
-        if result.is_err() {
-            let err_msg = format!("Failed to decode state: {:?}", result.err());
-            error!("{}", err_msg.to_string());
-            return BooleanActorResponse::Err(ErrorInfo::from(CommonError::Unknown {
-                detail: err_msg,
-            }));
-        }
-        let new_state = result.unwrap();
-        s.replace(new_state);
+    if let Err(err) = must_be_state_loader(caller) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    let result = from_state_export_data(request).and_then(replace_state);
+    if result.is_ok() {
         info!("load_state: success");
-        return BooleanActorResponse::Ok(true);
-    })
+    }
+    BooleanActorResponse::new(result)
+}
+
+#[update(name = "begin_state_export")]
+#[candid_method(update, rename = "begin_state_export")]
+pub async fn begin_state_export() -> StateTransferSessionResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_named_principal(caller, PRINCIPAL_NAME_STATE_EXPORTER) {
+        return StateTransferSessionResponse::new(Err(err));
+    }
+    let result = export_state_data().await.and_then(|export_data| {
+        STATE_TRANSFER_SESSIONS.with(|sessions| {
+            sessions
+                .borrow_mut()
+                .begin_export(export_data, TimeInNs(api::time()))
+                .map_err(CommonError::from)
+        })
+    });
+    if let Ok(session) = &result {
+        info!(
+            "begin_state_export: session {} with {} bytes in {} chunks",
+            session.session_id, session.total_size, session.chunk_count
+        );
+    }
+    StateTransferSessionResponse::new(result)
+}
+
+#[query(name = "get_state_export_chunk")]
+#[candid_method(query, rename = "get_state_export_chunk")]
+pub fn get_state_export_chunk(session_id: u64, index: u64) -> StateChunkResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_named_principal(caller, PRINCIPAL_NAME_STATE_EXPORTER) {
+        return StateChunkResponse::new(Err(err));
+    }
+    let result = STATE_TRANSFER_SESSIONS.with(|sessions| {
+        sessions
+            .borrow()
+            .get_export_chunk(session_id, index)
+            .map_err(CommonError::from)
+    });
+    StateChunkResponse::new(result)
+}
+
+#[update(name = "finish_state_export")]
+#[candid_method(update, rename = "finish_state_export")]
+pub fn finish_state_export(session_id: u64) -> BooleanActorResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_named_principal(caller, PRINCIPAL_NAME_STATE_EXPORTER) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    let result = STATE_TRANSFER_SESSIONS.with(|sessions| {
+        sessions
+            .borrow_mut()
+            .finish_export(session_id)
+            .map(|_| true)
+            .map_err(CommonError::from)
+    });
+    BooleanActorResponse::new(result)
+}
+
+#[update(name = "begin_state_import")]
+#[candid_method(update, rename = "begin_state_import")]
+pub fn begin_state_import(request: BeginStateImportRequest) -> StateTransferSessionResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_state_loader(caller) {
+        return StateTransferSessionResponse::new(Err(err));
+    }
+    let result = STATE_TRANSFER_SESSIONS.with(|sessions| {
+        sessions
+            .borrow_mut()
+            .begin_import(request, TimeInNs(api::time()))
+            .map_err(CommonError::from)
+    });
+    StateTransferSessionResponse::new(result)
+}
+
+#[update(name = "upload_state_chunk")]
+#[candid_method(update, rename = "upload_state_chunk")]
+pub fn upload_state_chunk(chunk: StateChunk) -> BooleanActorResponse {
+    debug!("upload_state_chunk: {}", chunk);
+    let caller = &api::caller();
+    if let Err(err) = must_be_state_loader(caller) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    let result = STATE_TRANSFER_SESSIONS.with(|sessions| {
+        sessions
+            .borrow_mut()
+            .upload_chunk(chunk)
+            .map(|_| true)
+            .map_err(CommonError::from)
+    });
+    BooleanActorResponse::new(result)
+}
+
+#[update(name = "finalize_state_import")]
+#[candid_method(update, rename = "finalize_state_import")]
+pub fn finalize_state_import(session_id: u64) -> BooleanActorResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_state_loader(caller) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    let result = STATE_TRANSFER_SESSIONS
+        .with(|sessions| {
+            sessions
+                .borrow_mut()
+                .finalize_import(session_id)
+                .map_err(CommonError::from)
+        })
+        .and_then(from_state_export_data)
+        .and_then(replace_state);
+    if result.is_ok() {
+        info!("finalize_state_import: session {} loaded", session_id);
+    }
+    BooleanActorResponse::new(result)
+}
+
+#[update(name = "take_state_snapshot")]
+#[candid_method(update, rename = "take_state_snapshot")]
+pub fn take_state_snapshot() -> StateSnapshotResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_system_owner(caller) {
+        return StateSnapshotResponse::new(Err(err));
+    }
+    let data = STATE.with(|state| state.encode());
+    let result = StateSnapshots::default()
+        .take(data, TimeInNs(api::time()), env!("GIT_COMMIT"))
+        .map_err(CommonError::from);
+    if let Ok(info) = &result {
+        info!(
+            "take_state_snapshot: snapshot {} with {} bytes",
+            info.id, info.size
+        );
+    }
+    StateSnapshotResponse::new(result)
+}
+
+#[query(name = "list_state_snapshots")]
+#[candid_method(query, rename = "list_state_snapshots")]
+pub fn list_state_snapshots() -> StateSnapshotListResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_system_owner(caller) {
+        return StateSnapshotListResponse::new(Err(err));
+    }
+    let result = StateSnapshots::default().list().map_err(CommonError::from);
+    StateSnapshotListResponse::new(result)
+}
+
+#[update(name = "restore_state_snapshot")]
+#[candid_method(update, rename = "restore_state_snapshot")]
+pub fn restore_state_snapshot(id: u64) -> BooleanActorResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_system_owner(caller) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    let result = StateSnapshots::default()
+        .get(id)
+        .map_err(CommonError::from)
+        .and_then(replace_state);
+    if result.is_ok() {
+        info!("restore_state_snapshot: snapshot {} restored", id);
+    }
+    BooleanActorResponse::new(result)
+}
+
+#[update(name = "delete_state_snapshot")]
+#[candid_method(update, rename = "delete_state_snapshot")]
+pub fn delete_state_snapshot(id: u64) -> BooleanActorResponse {
+    let caller = &api::caller();
+    if let Err(err) = must_be_system_owner(caller) {
+        return BooleanActorResponse::new(Err(err));
+    }
+    let result = StateSnapshots::default()
+        .delete(id)
+        .map(|_| true)
+        .map_err(CommonError::from);
+    BooleanActorResponse::new(result)
+}
+
+/// Serves `/metrics` in the Prometheus text format, `/wasm_info` and the routes added with
+/// [crate::router::extend_router]. Only `/wasm_info` is certified, scrape the raw url of the
+/// canister for metrics.
+#[query(name = "http_request")]
+#[candid_method(query, rename = "http_request")]
+pub fn http_request(request: HttpRequest) -> HttpResponse {
+    handle_http_request(&request)
+}
+
+#[query(name = "http_request_streaming_callback")]
+#[candid_method(query, rename = "http_request_streaming_callback")]
+pub fn http_request_streaming_callback(token: Token) -> StreamingCallbackHttpResponse {
+    common::http::streaming::http_request_streaming_callback(token)
 }
 
 #[query(name = "get_wasm_info")]
 #[candid_method(query)]
-fn get_wasm_info() -> HashMap<&'static str, &'static str> {
+pub(crate) fn get_wasm_info() -> HashMap<&'static str, &'static str> {
     let mut map = HashMap::new();
     map.insert("BUILD_TIMESTAMP", env!("BUILD_TIMESTAMP"));
     map.insert("CARGO_PKG_VERSION", env!("CARGO_PKG_VERSION"));
@@ -1,3 +1,5 @@
 mod actor;
+mod metrics;
+pub mod router;
 mod state;
 mod stats_service;
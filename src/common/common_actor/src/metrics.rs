@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::io;
+
+use ic_cdk::api;
+use ic_cdk::api::stable::stable64_size;
+
+use common::metrics_encoder::{encode_registered_metrics, MetricsEncoder};
+use common::state::StableState;
+
+use crate::state::STATE;
+
+const WASM_PAGE_SIZE: u64 = 64 * 1024;
+
+/// Encodes the built-in metrics of the canister followed by the registered metrics.
+///
+/// `canister_state_bytes` encodes the state on every scrape. Metrics are served by a query, so the
+/// cost is only paid when they are read instead of on every update.
+pub fn encode_metrics(wasm_info: HashMap<&'static str, &'static str>) -> io::Result<Vec<u8>> {
+    let now_millis = (api::time() / 1_000_000) as i64;
+    let mut encoder = MetricsEncoder::new(vec![], now_millis);
+    encoder.encode_gauge(
+        "canister_cycles_balance",
+        api::canister_balance128() as f64,
+        "Cycles balance of the canister.",
+    )?;
+    encoder.encode_gauge(
+        "canister_heap_memory_bytes",
+        heap_memory_size() as f64,
+        "Size of the heap memory of the canister in bytes.",
+    )?;
+    encoder.encode_gauge(
+        "canister_stable_memory_bytes",
+        (stable64_size() * WASM_PAGE_SIZE) as f64,
+        "Size of the stable memory of the canister in bytes.",
+    )?;
+    encoder.encode_gauge(
+        "canister_state_bytes",
+        STATE.with(|state| state.encode().len()) as f64,
+        "Size of the encoded state in bytes.",
+    )?;
+
+    let mut labels: Vec<(String, &str)> = wasm_info
+        .into_iter()
+        .map(|(name, value)| (name.to_lowercase(), value))
+        .collect();
+    labels.sort();
+    let labels: Vec<(&str, &str)> = labels
+        .iter()
+        .map(|(name, value)| (name.as_str(), *value))
+        .collect();
+    encoder.encode_info(
+        "canister_build_info",
+        &labels,
+        "Build information of the canister wasm.",
+    )?;
+
+    encode_registered_metrics(&mut encoder)?;
+    Ok(encoder.into_inner())
+}
+
+fn heap_memory_size() -> u64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        core::arch::wasm32::memory_size(0) as u64 * WASM_PAGE_SIZE
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        0
+    }
+}
@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+
+use common::errors::CommonError;
+use common::http::certification::{certify_hook, certify_route};
+use common::http::{HeaderField, HttpRequest, HttpResponse, HttpResult, Router};
+
+use crate::actor::get_wasm_info;
+use crate::metrics::encode_metrics;
+
+/// Paths whose response only changes with the wasm, certified in `init` and `post_upgrade`.
+const CERTIFIED_PATHS: [&str; 1] = ["/wasm_info"];
+
+thread_local! {
+    static ROUTER: RefCell<Router> = RefCell::new(
+        Router::new()
+            .get("/metrics", |_| get_metrics())
+            .get("/wasm_info", |_| get_wasm_info_text())
+            .after(certify_hook)
+    );
+}
+
+/// Adds routes or hooks to `http_request`, in `init` and `post_upgrade` since the router is not
+/// kept across upgrades.
+///
+/// ```ignore
+/// extend_router(|router| router.get("/names/{name}", |req| get_name(req.param("name").unwrap())));
+/// ```
+///
+/// Routes are matched after the built-in ones and their responses pass the certification hook,
+/// certify them with [certify_path].
+pub fn extend_router<F>(extend: F)
+where
+    F: FnOnce(Router) -> Router,
+{
+    ROUTER.with(|router| {
+        let current = router.take();
+        router.replace(extend(current));
+    });
+}
+
+/// Certifies the response of `path`, see [certify_route].
+pub fn certify_path(path: &str) {
+    ROUTER.with(|router| certify_route(&router.borrow(), path));
+}
+
+pub(crate) fn certify_routes() {
+    for path in CERTIFIED_PATHS {
+        certify_path(path);
+    }
+}
+
+pub(crate) fn handle_http_request(request: &HttpRequest) -> HttpResponse {
+    ROUTER.with(|router| router.borrow().handle(request))
+}
+
+fn get_metrics() -> HttpResult {
+    let body = encode_metrics(get_wasm_info()).map_err(|err| CommonError::Unknown {
+        detail: err.to_string(),
+    })?;
+    let mut response = HttpResponse::new(200, body);
+    response.headers.push(HeaderField(
+        "Content-Type".to_string(),
+        "text/plain; version=0.0.4".to_string(),
+    ));
+    Ok(response)
+}
+
+/// `get_wasm_info` as `KEY=value` lines, sorted by key.
+fn get_wasm_info_text() -> HttpResult {
+    let mut info: Vec<(&str, &str)> = get_wasm_info().into_iter().collect();
+    info.sort();
+    let body: String = info
+        .into_iter()
+        .map(|(key, value)| format!("{}={}\n", key, value))
+        .collect();
+    let mut response = HttpResponse::string(200, &body);
+    response.headers.push(HeaderField(
+        "Content-Type".to_string(),
+        "text/plain; charset=utf-8".to_string(),
+    ));
+    Ok(response)
+}
@@ -1,27 +1,24 @@
+use std::cell::RefCell;
+
 use common::state::StableState;
 
 thread_local! {
     pub static STATE : State = State::default();
 }
 
-#[derive(Default)]
+#[derive(Default, StableState)]
 pub struct State {
     // NOTE: When adding new persistent fields here, ensure that these fields
     // are being persisted in the `replace` method below.
+    /// Hex encoded X25519 public key of the state exporter set by the owner, overrides the key
+    /// configured at build time.
+    #[stable(since = 2)]
+    pub state_exporter_public_key: RefCell<Option<String>>,
 }
 
 impl State {
-    pub fn replace(&self, _new_state: State) {
-        unreachable!("State should not be replaced");
-    }
-}
-
-impl StableState for State {
-    fn encode(&self) -> Vec<u8> {
-        unreachable!("State should not be encoded");
-    }
-
-    fn decode(_bytes: Vec<u8>) -> Result<Self, String> {
-        unreachable!("State::decode should never be called");
+    pub fn replace(&self, new_state: State) {
+        self.state_exporter_public_key
+            .replace(new_state.state_exporter_public_key.take());
     }
 }
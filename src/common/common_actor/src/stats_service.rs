@@ -11,3 +11,5 @@ impl StatsService {
 
 #[derive(CandidType, Deserialize)]
 pub struct Stats {}
+
+common::actor_response!(pub GetStatsResponse(Stats));
@@ -0,0 +1,297 @@
+use std::collections::BTreeMap;
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::ParseStream;
+use syn::{Attribute, Data, DeriveInput, Expr, ExprLit, Fields, Ident, Index, Lit, LitStr};
+
+use crate::{expect_flag, expect_u32, expect_value, parse_attr_args, AttrArg};
+
+/// Keep in sync with `common::errors::ERROR_NAMESPACE_SIZE`.
+const ERROR_NAMESPACE_SIZE: u32 = 100_000;
+
+/// Status of errors without `http_status`.
+const DEFAULT_HTTP_STATUS: u16 = 500;
+
+struct EnumOptions {
+    namespace: u32,
+    name: String,
+    http_status: u16,
+}
+
+impl EnumOptions {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut namespace = None;
+        let mut name = None;
+        let mut http_status = DEFAULT_HTTP_STATUS;
+        for arg in parse_attr_args(&input.attrs, "error_code")? {
+            if arg.name == "namespace" {
+                namespace = Some(expect_u32(arg, "expected an integer namespace")?);
+            } else if arg.name == "name" {
+                match expect_value(arg)? {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit), ..
+                    }) => name = Some(lit.value()),
+                    value => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "expected a string namespace name",
+                        ))
+                    }
+                }
+            } else if arg.name == "http_status" {
+                http_status = expect_http_status(arg)?;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &arg.name,
+                    "unknown attribute, expected `namespace`, `name` or `http_status`",
+                ));
+            }
+        }
+        let missing = || {
+            syn::Error::new_spanned(
+                &input.ident,
+                "missing `#[error_code(namespace = N, name = \"...\")]`",
+            )
+        };
+        let namespace = namespace.ok_or_else(missing)?;
+        if namespace > u32::MAX / ERROR_NAMESPACE_SIZE - 1 {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                format!("namespace {} is too large", namespace),
+            ));
+        }
+        Ok(EnumOptions {
+            namespace,
+            name: name.ok_or_else(missing)?,
+            http_status,
+        })
+    }
+}
+
+fn expect_http_status(arg: AttrArg) -> syn::Result<u16> {
+    let name = arg.name.clone();
+    let status = expect_u32(arg, "expected an integer http status")?;
+    if !(100..600).contains(&status) {
+        return Err(syn::Error::new_spanned(
+            name,
+            format!("{} is not a valid http status", status),
+        ));
+    }
+    Ok(status as u16)
+}
+
+struct VariantOptions {
+    code: u32,
+    http_status: Option<u16>,
+    inner_http_status: bool,
+}
+
+impl VariantOptions {
+    fn parse(variant: &syn::Variant) -> syn::Result<Self> {
+        let mut code = None;
+        let mut http_status = None;
+        let mut inner_http_status = false;
+        for arg in parse_attr_args(&variant.attrs, "error_code")? {
+            if arg.name == "code" {
+                code = Some(expect_u32(arg, "expected an integer code")?);
+            } else if arg.name == "http_status" {
+                http_status = Some(expect_http_status(arg)?);
+            } else if arg.name == "inner_http_status" {
+                expect_flag(arg)?;
+                inner_http_status = true;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &arg.name,
+                    "unknown attribute, expected `code`, `http_status` or `inner_http_status`",
+                ));
+            }
+        }
+        if inner_http_status {
+            if http_status.is_some() {
+                return Err(syn::Error::new_spanned(
+                    &variant.ident,
+                    "`inner_http_status` can not be combined with `http_status`",
+                ));
+            }
+            if !matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1) {
+                return Err(syn::Error::new_spanned(
+                    &variant.ident,
+                    "`inner_http_status` requires a variant with a single unnamed field",
+                ));
+            }
+        }
+        let code = code.ok_or_else(|| {
+            syn::Error::new_spanned(&variant.ident, "missing `#[error_code(code = N)]`")
+        })?;
+        if code >= ERROR_NAMESPACE_SIZE {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!("error code must be less than {}", ERROR_NAMESPACE_SIZE),
+            ));
+        }
+        Ok(VariantOptions {
+            code,
+            http_status,
+            inner_http_status,
+        })
+    }
+}
+
+/// Returns whether a variant field is marked with `#[error_code(skip)]`.
+fn skip_field(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for arg in parse_attr_args(&field.attrs, "error_code")? {
+        if arg.name == "skip" {
+            expect_flag(arg)?;
+            skip = true;
+        } else {
+            return Err(syn::Error::new_spanned(
+                &arg.name,
+                "unknown attribute, expected `skip`",
+            ));
+        }
+    }
+    Ok(skip)
+}
+
+/// Returns the match arm inserting the fields of a variant into `details`, keyed by field name,
+/// or by index for tuple variants.
+fn details_arm(name: &Ident, variant: &syn::Variant) -> syn::Result<TokenStream2> {
+    let ident = &variant.ident;
+    let mut bindings = vec![];
+    let mut inserts = vec![];
+    for (index, field) in variant.fields.iter().enumerate() {
+        if skip_field(field)? {
+            continue;
+        }
+        let member = match &field.ident {
+            Some(field_ident) => quote! { #field_ident },
+            None => {
+                let index = Index::from(index);
+                quote! { #index }
+            }
+        };
+        let key = match &field.ident {
+            Some(field_ident) => field_ident.to_string(),
+            None => index.to_string(),
+        };
+        let binding = format_ident!("field_{}", index);
+        bindings.push(quote! { #member: #binding });
+        inserts.push(quote! {
+            details.insert(
+                ::std::string::ToString::to_string(#key),
+                ::std::string::ToString::to_string(#binding),
+            );
+        });
+    }
+    Ok(quote! {
+        #name::#ident { #(#bindings,)* .. } => {
+            #(#inserts)*
+        }
+    })
+}
+
+/// Returns the message template of a thiserror `#[error("...")]` attribute.
+fn error_message(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("error"))
+        .find_map(|attr| {
+            attr.parse_args_with(|input: ParseStream| {
+                let message: LitStr = input.parse()?;
+                input.parse::<TokenStream2>()?;
+                Ok(message.value())
+            })
+            .ok()
+        })
+        .unwrap_or_default()
+}
+
+pub(crate) fn expand_error_code(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "ErrorCode can only be derived for enums",
+            ))
+        }
+    };
+    let options = EnumOptions::parse(&input)?;
+
+    let mut codes: BTreeMap<u32, &syn::Ident> = BTreeMap::new();
+    let mut arms = vec![];
+    let mut status_arms = vec![];
+    let mut details_arms = vec![];
+    let mut entries = vec![];
+    for variant in variants.iter() {
+        let ident = &variant.ident;
+        let variant_options = VariantOptions::parse(variant)?;
+        let code = variant_options.code;
+        if let Some(existing) = codes.insert(code, ident) {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!("error code {} is already used by {}", code, existing),
+            ));
+        }
+        let pattern = match &variant.fields {
+            Fields::Unit => quote! { #name::#ident },
+            Fields::Unnamed(_) => quote! { #name::#ident(..) },
+            Fields::Named(_) => quote! { #name::#ident { .. } },
+        };
+        arms.push(quote! { #pattern => #code });
+        if variant_options.inner_http_status {
+            status_arms.push(quote! {
+                #name::#ident(inner) => ::common::errors::HttpStatus::http_status(inner)
+            });
+        } else {
+            let http_status = variant_options.http_status.unwrap_or(options.http_status);
+            status_arms.push(quote! { #pattern => #http_status });
+        }
+        details_arms.push(details_arm(name, variant)?);
+        let variant_name = ident.to_string();
+        let message = error_message(&variant.attrs);
+        entries.push(quote! {
+            ::common::errors::ErrorCodeEntry::new::<Self>(#code, #variant_name, #message)
+        });
+    }
+
+    let namespace = options.namespace;
+    let namespace_name = options.name;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::common::errors::ErrorCode for #name #ty_generics #where_clause {
+            const NAMESPACE: u32 = #namespace;
+            const NAMESPACE_NAME: &'static str = #namespace_name;
+
+            fn local_code(&self) -> u32 {
+                match self {
+                    #(#arms,)*
+                }
+            }
+
+            fn http_status(&self) -> u16 {
+                match self {
+                    #(#status_arms,)*
+                }
+            }
+
+            fn details(
+                &self,
+            ) -> ::std::collections::BTreeMap<::std::string::String, ::std::string::String> {
+                #[allow(unused_mut)]
+                let mut details = ::std::collections::BTreeMap::new();
+                match self {
+                    #(#details_arms)*
+                }
+                details
+            }
+
+            fn catalog() -> ::std::vec::Vec<::common::errors::ErrorCodeEntry> {
+                ::std::vec![#(#entries,)*]
+            }
+        }
+    })
+}
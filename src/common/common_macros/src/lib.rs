@@ -0,0 +1,406 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Expr, ExprLit, ExprPath, Fields,
+    GenericArgument, Ident, Lit, PathArguments, Token, Type,
+};
+
+use crate::error_code::expand_error_code;
+
+mod error_code;
+
+/// Largest tuple implementing candid's `ArgumentEncoder` and `ArgumentDecoder`.
+const MAX_PERSISTED_FIELDS: usize = 16;
+
+/// Derives `common::state::StableState` for a struct whose fields are wrapped in `RefCell`.
+///
+/// Persisted fields can not contain a `HashMap` or `HashSet`: their entries are encoded in hash
+/// order, which changes when the state is reloaded and would change the state hash. Use a
+/// `BTreeMap` or `BTreeSet` instead, skipped fields are not restricted.
+///
+/// Fields are encoded as a candid argument tuple in declaration order, so reordering fields
+/// breaks decoding of existing states. New fields must be appended with `#[stable(since = N)]`:
+/// they are encoded as `opt` and fall back to `default` (or `Default::default()`) when missing
+/// from a state written by an older version.
+///
+/// At most 16 fields can be persisted, the largest tuple candid encodes as arguments. Group fields
+/// into a struct to persist more, or skip the ones which can be rebuilt.
+///
+/// ```ignore
+/// #[derive(Default, StableState)]
+/// #[stable(version = 2, migrations = state_migrations)]
+/// pub struct State {
+///     registries: RefCell<BTreeMap<String, Principal>>,
+///     #[stable(since = 2, default = 100)]
+///     limit: RefCell<u32>,
+///     #[stable(skip)]
+///     cache: RefCell<HashMap<String, u64>>,
+/// }
+/// ```
+///
+/// Struct attributes:
+/// - `version = N`: schema version, defaults to the highest `since` of all fields or 1.
+/// - `migrations = path`: function returning the `StateMigrations` of the state.
+///
+/// Field attributes:
+/// - `since = N`: schema version the field was added in.
+/// - `default = expr`: value used when the field is missing, requires `since`.
+/// - `skip`: the field is not persisted and is restored with `Default::default()`.
+///
+/// `#[stable_state(...)]` is accepted as an alias of `#[stable(...)]`.
+#[proc_macro_derive(StableState, attributes(stable, stable_state))]
+pub fn derive_stable_state(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_stable_state(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derives `common::errors::ErrorCode` for an error enum.
+///
+/// Every variant needs a stable code, unique within the namespace of the enum. The full code of an
+/// error is `namespace * 100_000 + code`, so errors of different enums can not collide as long as
+/// their namespaces differ. Codes must not be reused once released.
+///
+/// ```ignore
+/// #[derive(Error, ErrorCode)]
+/// #[error_code(namespace = 1, name = "mock")]
+/// pub enum MockError {
+///     #[error("owner is invalid")]
+///     #[error_code(code = 4)]
+///     InvalidOwner,
+/// }
+/// ```
+///
+/// The message template of the thiserror `#[error("...")]` attribute is included in the catalog.
+///
+/// `http_status = N` sets the status of http error responses, on a variant or as the default of
+/// the enum. Errors without a status are returned with 500. `inner_http_status` on a variant
+/// wrapping another error takes the status of the wrapped error, which implements
+/// `common::errors::HttpStatus`.
+///
+/// The fields of a variant are returned by `details`, formatted with `Display` and keyed by field
+/// name, or by index for tuple variants. Fields marked with `#[error_code(skip)]` are left out.
+#[proc_macro_derive(ErrorCode, attributes(error_code))]
+pub fn derive_error_code(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_error_code(input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+struct AttrArg {
+    name: Ident,
+    value: Option<Expr>,
+}
+
+impl Parse for AttrArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(AttrArg { name, value })
+    }
+}
+
+fn parse_stable_args(attrs: &[Attribute]) -> syn::Result<Vec<AttrArg>> {
+    let mut args = parse_attr_args(attrs, "stable")?;
+    args.extend(parse_attr_args(attrs, "stable_state")?);
+    Ok(args)
+}
+
+/// Parses the `name` or `name = value` arguments of all `#[attr_name(...)]` attributes.
+fn parse_attr_args(attrs: &[Attribute], attr_name: &str) -> syn::Result<Vec<AttrArg>> {
+    let mut args = vec![];
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident(attr_name)) {
+        let parsed = attr.parse_args_with(Punctuated::<AttrArg, Token![,]>::parse_terminated)?;
+        args.extend(parsed);
+    }
+    Ok(args)
+}
+
+fn expect_value(arg: AttrArg) -> syn::Result<Expr> {
+    let name = arg.name;
+    arg.value
+        .ok_or_else(|| syn::Error::new_spanned(&name, format!("`{}` requires a value", name)))
+}
+
+fn expect_version(arg: AttrArg) -> syn::Result<u32> {
+    expect_u32(arg, "expected an integer version")
+}
+
+fn expect_u32(arg: AttrArg, message: &str) -> syn::Result<u32> {
+    match expect_value(arg)? {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit), ..
+        }) => lit.base10_parse(),
+        value => Err(syn::Error::new_spanned(value, message)),
+    }
+}
+
+fn expect_flag(arg: AttrArg) -> syn::Result<()> {
+    match arg.value {
+        Some(value) => Err(syn::Error::new_spanned(
+            value,
+            format!("`{}` does not take a value", arg.name),
+        )),
+        None => Ok(()),
+    }
+}
+
+#[derive(Default)]
+struct StructOptions {
+    version: Option<u32>,
+    migrations: Option<ExprPath>,
+}
+
+impl StructOptions {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = StructOptions::default();
+        for arg in parse_stable_args(attrs)? {
+            if arg.name == "version" {
+                options.version = Some(expect_version(arg)?);
+            } else if arg.name == "migrations" {
+                match expect_value(arg)? {
+                    Expr::Path(path) => options.migrations = Some(path),
+                    value => {
+                        return Err(syn::Error::new_spanned(
+                            value,
+                            "expected a path to a migrations function",
+                        ))
+                    }
+                }
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &arg.name,
+                    "unknown attribute, expected `version` or `migrations`",
+                ));
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    since: Option<u32>,
+    default: Option<Expr>,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut options = FieldOptions::default();
+        for arg in parse_stable_args(attrs)? {
+            if arg.name == "since" {
+                options.since = Some(expect_version(arg)?);
+            } else if arg.name == "default" {
+                options.default = Some(expect_value(arg)?);
+            } else if arg.name == "skip" {
+                expect_flag(arg)?;
+                options.skip = true;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    &arg.name,
+                    "unknown attribute, expected `since`, `default` or `skip`",
+                ));
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Returns `T` of a `RefCell<T>` field.
+fn ref_cell_inner(ty: &Type) -> Option<&Type> {
+    let path = match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => &type_path.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "RefCell" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the name of a `HashMap` or `HashSet` in `ty`, whose candid encoding depends on the
+/// iteration order of the hasher.
+fn unordered_collection(ty: &Type) -> Option<String> {
+    quote!(#ty)
+        .into_iter()
+        .flat_map(flatten_tokens)
+        .find(|name| name == "HashMap" || name == "HashSet")
+}
+
+fn flatten_tokens(token: proc_macro2::TokenTree) -> Vec<String> {
+    match token {
+        proc_macro2::TokenTree::Group(group) => group
+            .stream()
+            .into_iter()
+            .flat_map(flatten_tokens)
+            .collect(),
+        proc_macro2::TokenTree::Ident(ident) => vec![ident.to_string()],
+        _ => vec![],
+    }
+}
+
+fn expand_stable_state(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input,
+                    "StableState can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "StableState can only be derived for structs",
+            ))
+        }
+    };
+    let struct_options = StructOptions::parse(&input.attrs)?;
+
+    let mut encoded = vec![];
+    let mut decoded_types = vec![];
+    let mut decoded_names = vec![];
+    let mut initializers = vec![];
+    let mut latest_since: Option<u32> = None;
+    for field in fields.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let options = FieldOptions::parse(&field.attrs)?;
+        if options.skip {
+            if options.since.is_some() || options.default.is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "`skip` can not be combined with `since` or `default`",
+                ));
+            }
+            initializers.push(quote! { #ident: ::core::default::Default::default() });
+            continue;
+        }
+        let inner = ref_cell_inner(&field.ty).ok_or_else(|| {
+            syn::Error::new_spanned(&field.ty, "persisted fields must be wrapped in RefCell")
+        })?;
+        if let Some(unordered) = unordered_collection(inner) {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                format!(
+                    "the encoding order of `{}` is not stable, persist a `BTreeMap` or `BTreeSet` instead",
+                    unordered
+                ),
+            ));
+        }
+
+        match options.since {
+            None => {
+                if let Some(default) = options.default {
+                    return Err(syn::Error::new_spanned(
+                        default,
+                        "`default` requires `since`",
+                    ));
+                }
+                if latest_since.is_some() {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "fields without `since` must be declared before fields with `since`",
+                    ));
+                }
+                encoded.push(quote! { &*self.#ident.borrow() });
+                decoded_types.push(quote! { #inner });
+                initializers.push(quote! { #ident: ::std::cell::RefCell::new(#ident) });
+            }
+            Some(since) => {
+                if latest_since.map_or(false, |latest| since < latest) {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "fields must be declared in the order of their `since` versions",
+                    ));
+                }
+                latest_since = Some(since);
+                let default = options
+                    .default
+                    .map(|default| quote! { #default })
+                    .unwrap_or_else(|| quote! { ::core::default::Default::default() });
+                encoded.push(quote! { ::core::option::Option::Some(&*self.#ident.borrow()) });
+                decoded_types.push(quote! { ::core::option::Option<#inner> });
+                initializers.push(quote! {
+                    #ident: ::std::cell::RefCell::new(#ident.unwrap_or_else(|| #default))
+                });
+            }
+        }
+        decoded_names.push(ident);
+    }
+
+    if encoded.len() > MAX_PERSISTED_FIELDS {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            format!(
+                "{} fields are persisted but candid encodes at most {} arguments, group fields \
+                 into a struct or skip some of them",
+                encoded.len(),
+                MAX_PERSISTED_FIELDS
+            ),
+        ));
+    }
+
+    let schema_version = match (struct_options.version, latest_since) {
+        (Some(version), Some(since)) if version < since => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                format!(
+                    "schema version {} is lower than the `since` version {} of a field",
+                    version, since
+                ),
+            ))
+        }
+        (Some(version), _) => version,
+        (None, since) => since.unwrap_or(1).max(1),
+    };
+    let migrations = struct_options.migrations.map(|migrations| {
+        quote! {
+            fn migrations() -> ::common::state::StateMigrations {
+                #migrations()
+            }
+        }
+    });
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::common::state::StableState for #name #ty_generics #where_clause {
+            const SCHEMA_VERSION: u32 = #schema_version;
+
+            #migrations
+
+            fn encode_payload(&self) -> ::std::vec::Vec<u8> {
+                ::candid::encode_args((#(#encoded,)*)).unwrap()
+            }
+
+            fn decode_payload(
+                bytes: ::std::vec::Vec<u8>,
+            ) -> ::core::result::Result<Self, ::common::state::StateDecodeError> {
+                let (#(#decoded_names,)*): (#(#decoded_types,)*) = ::candid::decode_args(&bytes)?;
+                ::core::result::Result::Ok(Self {
+                    #(#initializers,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,151 @@
+//! Encryption of exported state to the X25519 public key of the state exporter.
+//!
+//! Each export uses a fresh ephemeral key pair. The key and nonce of ChaCha20-Poly1305 are derived
+//! with HKDF-SHA256 from the X25519 shared secret, so only the holder of the exporter secret key
+//! can decrypt the export. Layout of an encrypted export:
+//!
+//! magic (4) + version (1) + ephemeral public key (32) + ciphertext with tag
+//!
+//! The header is authenticated as associated data.
+
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use thiserror::Error;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+#[cfg(test)]
+mod tests;
+
+/// Magic number at the start of every encrypted export.
+pub const ENCRYPTED_STATE_MAGIC: [u8; 4] = *b"CTSE";
+pub const ENCRYPTED_STATE_VERSION: u8 = 1;
+pub const KEY_SIZE: usize = 32;
+
+const HEADER_SIZE: usize = 4 + 1 + KEY_SIZE;
+const TAG_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const HKDF_INFO: &[u8] = b"canister-template state export v1";
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum StateCryptoError {
+    #[error("invalid key, {detail}")]
+    InvalidKey { detail: String },
+    #[error("encrypted state is too short, got {length} bytes")]
+    TooShort { length: usize },
+    #[error("invalid encrypted state magic number {magic:?}")]
+    InvalidMagic { magic: Vec<u8> },
+    #[error("unsupported encrypted state version {version}")]
+    UnsupportedVersion { version: u8 },
+    #[error("key exchange produced a non contributory shared secret")]
+    NonContributoryKey,
+    #[error("failed to encrypt state")]
+    EncryptFailed,
+    #[error("failed to decrypt state, wrong key or corrupted data")]
+    DecryptFailed,
+}
+
+/// Parses a hex encoded 32 bytes key.
+pub fn parse_key_hex(key: &str) -> Result<[u8; KEY_SIZE], StateCryptoError> {
+    let bytes = hex::decode(key.trim()).map_err(|err| StateCryptoError::InvalidKey {
+        detail: err.to_string(),
+    })?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| StateCryptoError::InvalidKey {
+            detail: format!("expected {} bytes, got {}", KEY_SIZE, bytes.len()),
+        })
+}
+
+/// Returns the public key to register for the exporter holding `secret_key`.
+pub fn public_key_from_secret(secret_key: [u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    PublicKey::from(&StaticSecret::from(secret_key)).to_bytes()
+}
+
+fn cipher_and_nonce(
+    shared_secret: &[u8; KEY_SIZE],
+    header: &[u8],
+) -> (ChaCha20Poly1305, [u8; NONCE_SIZE]) {
+    let hkdf = Hkdf::<Sha256>::new(Some(header), shared_secret);
+    let mut okm = [0u8; KEY_SIZE + NONCE_SIZE];
+    hkdf.expand(HKDF_INFO, &mut okm)
+        .expect("output length is valid for HKDF-SHA256");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&okm[..KEY_SIZE]));
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&okm[KEY_SIZE..]);
+    (cipher, nonce)
+}
+
+/// Encrypts `plaintext` to `recipient_public_key`.
+///
+/// `ephemeral_secret` must be 32 fresh random bytes, e.g. from the `raw_rand` of the management canister.
+pub fn encrypt(
+    plaintext: &[u8],
+    recipient_public_key: &[u8; KEY_SIZE],
+    ephemeral_secret: [u8; KEY_SIZE],
+) -> Result<Vec<u8>, StateCryptoError> {
+    let ephemeral_secret = StaticSecret::from(ephemeral_secret);
+    let ephemeral_public_key = PublicKey::from(&ephemeral_secret);
+    let shared_secret = ephemeral_secret.diffie_hellman(&PublicKey::from(*recipient_public_key));
+    if !shared_secret.was_contributory() {
+        return Err(StateCryptoError::NonContributoryKey);
+    }
+
+    let mut encrypted = Vec::with_capacity(HEADER_SIZE + plaintext.len() + TAG_SIZE);
+    encrypted.extend_from_slice(&ENCRYPTED_STATE_MAGIC);
+    encrypted.push(ENCRYPTED_STATE_VERSION);
+    encrypted.extend_from_slice(ephemeral_public_key.as_bytes());
+
+    let (cipher, nonce) = cipher_and_nonce(shared_secret.as_bytes(), &encrypted);
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &encrypted,
+            },
+        )
+        .map_err(|_| StateCryptoError::EncryptFailed)?;
+    encrypted.extend_from_slice(&ciphertext);
+    Ok(encrypted)
+}
+
+/// Returns true if `data` starts like an export encrypted by [encrypt].
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(&ENCRYPTED_STATE_MAGIC)
+}
+
+/// Decrypts data encrypted by [encrypt] with the secret key of the recipient.
+pub fn decrypt(data: &[u8], secret_key: &[u8; KEY_SIZE]) -> Result<Vec<u8>, StateCryptoError> {
+    if data.len() < HEADER_SIZE + TAG_SIZE {
+        return Err(StateCryptoError::TooShort { length: data.len() });
+    }
+    let (header, ciphertext) = data.split_at(HEADER_SIZE);
+    if header[0..4] != ENCRYPTED_STATE_MAGIC {
+        return Err(StateCryptoError::InvalidMagic {
+            magic: header[0..4].to_vec(),
+        });
+    }
+    if header[4] != ENCRYPTED_STATE_VERSION {
+        return Err(StateCryptoError::UnsupportedVersion { version: header[4] });
+    }
+    let mut ephemeral_public_key = [0u8; KEY_SIZE];
+    ephemeral_public_key.copy_from_slice(&header[5..]);
+
+    let secret_key = StaticSecret::from(*secret_key);
+    let shared_secret = secret_key.diffie_hellman(&PublicKey::from(ephemeral_public_key));
+    if !shared_secret.was_contributory() {
+        return Err(StateCryptoError::NonContributoryKey);
+    }
+    let (cipher, nonce) = cipher_and_nonce(shared_secret.as_bytes(), header);
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| StateCryptoError::DecryptFailed)
+}
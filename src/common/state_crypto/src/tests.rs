@@ -0,0 +1,78 @@
+use rstest::*;
+
+use super::*;
+
+const RECIPIENT_SECRET: [u8; KEY_SIZE] = [7u8; KEY_SIZE];
+const EPHEMERAL_SECRET: [u8; KEY_SIZE] = [9u8; KEY_SIZE];
+
+#[fixture]
+fn recipient_public_key() -> [u8; KEY_SIZE] {
+    public_key_from_secret(RECIPIENT_SECRET)
+}
+
+#[rstest]
+fn test_encrypt_and_decrypt(recipient_public_key: [u8; KEY_SIZE]) {
+    let plaintext = b"state data".to_vec();
+    let encrypted = encrypt(&plaintext, &recipient_public_key, EPHEMERAL_SECRET).unwrap();
+
+    assert!(is_encrypted(&encrypted));
+    assert_eq!(encrypted.len(), HEADER_SIZE + plaintext.len() + TAG_SIZE);
+    assert_eq!(decrypt(&encrypted, &RECIPIENT_SECRET), Ok(plaintext));
+}
+
+#[rstest]
+fn test_decrypt_with_wrong_key(recipient_public_key: [u8; KEY_SIZE]) {
+    let encrypted = encrypt(b"state data", &recipient_public_key, EPHEMERAL_SECRET).unwrap();
+    assert_eq!(
+        decrypt(&encrypted, &[8u8; KEY_SIZE]),
+        Err(StateCryptoError::DecryptFailed)
+    );
+}
+
+#[rstest]
+fn test_decrypt_rejects_tampered_data(recipient_public_key: [u8; KEY_SIZE]) {
+    let mut encrypted = encrypt(b"state data", &recipient_public_key, EPHEMERAL_SECRET).unwrap();
+    let last = encrypted.len() - 1;
+    encrypted[last] ^= 1;
+    assert_eq!(
+        decrypt(&encrypted, &RECIPIENT_SECRET),
+        Err(StateCryptoError::DecryptFailed)
+    );
+
+    let mut encrypted = encrypt(b"state data", &recipient_public_key, EPHEMERAL_SECRET).unwrap();
+    encrypted[4] = 2;
+    assert_eq!(
+        decrypt(&encrypted, &RECIPIENT_SECRET),
+        Err(StateCryptoError::UnsupportedVersion { version: 2 })
+    );
+}
+
+#[rstest]
+fn test_decrypt_rejects_short_data() {
+    assert_eq!(
+        decrypt(&ENCRYPTED_STATE_MAGIC, &RECIPIENT_SECRET),
+        Err(StateCryptoError::TooShort { length: 4 })
+    );
+}
+
+#[rstest]
+fn test_encrypt_rejects_low_order_public_key() {
+    assert_eq!(
+        encrypt(b"state data", &[0u8; KEY_SIZE], EPHEMERAL_SECRET),
+        Err(StateCryptoError::NonContributoryKey)
+    );
+}
+
+#[rstest]
+fn test_parse_key_hex(recipient_public_key: [u8; KEY_SIZE]) {
+    assert_eq!(
+        parse_key_hex(&hex::encode(recipient_public_key)),
+        Ok(recipient_public_key)
+    );
+    assert_eq!(
+        parse_key_hex("0102"),
+        Err(StateCryptoError::InvalidKey {
+            detail: "expected 32 bytes, got 2".to_string()
+        })
+    );
+}
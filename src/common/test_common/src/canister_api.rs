@@ -46,6 +46,7 @@ mock! {
         async fn canister_install(&self, canister_id: &Principal,wasm_module: Vec<u8>,args: Vec<u8>) -> ActorResult<()>;
         async fn ecdsa_public_key(&self, get_public_key_req: ECDSAPublicKey) -> ActorResult<ECDSAPublicKeyReply>;
         async fn sign_with_ecdsa(&self, sign_request: SignWithECDSA) -> ActorResult<SignWithECDSAReply>;
+        async fn raw_rand(&self) -> ActorResult<Vec<u8>>;
     }
 }
 
@@ -0,0 +1,138 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::state::StableState;
+use log::info;
+
+#[cfg(test)]
+mod tests;
+
+/// Set this environment variable to record missing golden files and overwrite recorded ones.
+pub const UPDATE_GOLDEN_FILES_ENV: &str = "UPDATE_GOLDEN_FILES";
+
+const GOLDEN_FILE_EXTENSION: &str = "state";
+
+/// Encoded states checked in as `<name>.v<schema_version>.state` files.
+///
+/// Golden files are recorded once per schema version and kept afterwards, so every later build
+/// verifies that the states written by older versions still decode with the current types.
+/// Recording is explicit: a file of the current schema version which is missing fails the test,
+/// so a schema version bump without a checked in golden file is caught.
+pub struct StateGoldenFiles {
+    dir: PathBuf,
+    update: bool,
+}
+
+impl StateGoldenFiles {
+    /// Golden files in `dir`, recorded if [UPDATE_GOLDEN_FILES_ENV] is set.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            update: std::env::var_os(UPDATE_GOLDEN_FILES_ENV).is_some(),
+        }
+    }
+
+    /// Overrides whether [StateGoldenFiles::record] writes files.
+    pub fn update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    fn path(&self, name: &str, schema_version: u32) -> PathBuf {
+        assert!(
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+            "golden file name {:?} must only contain ascii letters, digits, '_' and '-'",
+            name
+        );
+        self.dir.join(format!(
+            "{}.v{}.{}",
+            name, schema_version, GOLDEN_FILE_EXTENSION
+        ))
+    }
+
+    /// Records `state` as golden file `name` of the current schema version when updating.
+    ///
+    /// Otherwise the existing file is kept, and a missing file panics: run the tests once with
+    /// [UPDATE_GOLDEN_FILES_ENV] set and check the new file in.
+    pub fn record<S: StableState>(&self, name: &str, state: &S) -> PathBuf {
+        let path = self.path(name, S::SCHEMA_VERSION);
+        if !self.update {
+            assert!(
+                path.exists(),
+                "golden file {} of schema version {} is missing, run the tests with {}=1 to \
+                 record it and check it in",
+                path.display(),
+                S::SCHEMA_VERSION,
+                UPDATE_GOLDEN_FILES_ENV
+            );
+            return path;
+        }
+        fs::create_dir_all(&self.dir)
+            .unwrap_or_else(|err| panic!("failed to create {}: {}", self.dir.display(), err));
+        fs::write(&path, state.encode())
+            .unwrap_or_else(|err| panic!("failed to write {}: {}", path.display(), err));
+        info!("golden file recorded: {}", path.display());
+        path
+    }
+
+    /// Returns all golden files in the directory, sorted by file name.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |extension| extension == GOLDEN_FILE_EXTENSION)
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Decodes every golden file with the current `S`, panics with all files which fail to decode.
+    pub fn assert_decodable<S: StableState>(&self) -> Vec<(PathBuf, S)> {
+        let mut states = vec![];
+        let mut failures = vec![];
+        for path in self.files() {
+            let bytes = fs::read(&path)
+                .unwrap_or_else(|err| panic!("failed to read {}: {}", path.display(), err));
+            match S::decode(bytes) {
+                Ok(state) => states.push((path, state)),
+                Err(err) => failures.push(format!("{}: {}", file_name(&path), err)),
+            }
+        }
+        assert!(
+            failures.is_empty(),
+            "golden states no longer decode with schema version {}, \
+             keep the layout compatible or register a migration:\n{}",
+            S::SCHEMA_VERSION,
+            failures.join("\n")
+        );
+        states
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+/// Records `fixtures` for the current schema version, then asserts that all golden files in `dir`
+/// still decode. Pass a directory inside the crate, e.g. `concat!(env!("CARGO_MANIFEST_DIR"), "/golden")`.
+pub fn assert_state_compatible<S: StableState>(
+    dir: &str,
+    fixtures: &[(&str, S)],
+) -> Vec<(PathBuf, S)> {
+    let golden_files = StateGoldenFiles::new(dir);
+    for (name, state) in fixtures {
+        golden_files.record(name, state);
+    }
+    golden_files.assert_decodable()
+}
@@ -0,0 +1,125 @@
+use std::cell::RefCell;
+
+use common::state::StableState;
+use rstest::*;
+
+use super::*;
+use crate::ic_api::init_test;
+
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+struct StateV1 {
+    counter: RefCell<u64>,
+}
+
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+struct StateV2 {
+    counter: RefCell<u64>,
+    #[stable(since = 2, default = 10)]
+    limit: RefCell<u32>,
+}
+
+#[derive(Default, StableState, Debug, Eq, PartialEq)]
+struct IncompatibleState {
+    counter: RefCell<String>,
+}
+
+fn golden_files(test_name: &str) -> StateGoldenFiles {
+    init_test();
+    let dir =
+        std::env::temp_dir().join(format!("state_golden_{}_{}", test_name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    StateGoldenFiles::new(dir).update(true)
+}
+
+fn state_v1(counter: u64) -> StateV1 {
+    StateV1 {
+        counter: RefCell::new(counter),
+    }
+}
+
+#[rstest]
+fn test_record_and_decode() {
+    let golden_files = golden_files("record_and_decode");
+    let path = golden_files.record("basic", &state_v1(1));
+    assert_eq!(file_name(&path), "basic.v1.state");
+
+    let states = golden_files.assert_decodable::<StateV1>();
+    assert_eq!(states, vec![(path, state_v1(1))]);
+}
+
+#[rstest]
+fn test_recorded_file_is_kept() {
+    let golden_files = golden_files("recorded_file_is_kept");
+    golden_files.record("basic", &state_v1(1));
+    let golden_files = golden_files.update(false);
+    golden_files.record("basic", &state_v1(2));
+
+    let states = golden_files.assert_decodable::<StateV1>();
+    assert_eq!(states[0].1, state_v1(1));
+}
+
+#[rstest]
+fn test_recorded_file_is_updated() {
+    let golden_files = golden_files("recorded_file_is_updated");
+    golden_files.record("basic", &state_v1(1));
+    golden_files.record("basic", &state_v1(2));
+
+    let states = golden_files.assert_decodable::<StateV1>();
+    assert_eq!(states[0].1, state_v1(2));
+}
+
+#[rstest]
+#[should_panic(expected = "basic.v2.state of schema version 2 is missing")]
+fn test_missing_file_of_current_version_fails() {
+    let golden_files = golden_files("missing_file");
+    golden_files.record("basic", &state_v1(1));
+    golden_files
+        .update(false)
+        .record("basic", &StateV2::default());
+}
+
+#[rstest]
+fn test_older_versions_decode_with_current_state() {
+    let golden_files = golden_files("older_versions");
+    golden_files.record("basic", &state_v1(1));
+    golden_files.record(
+        "basic",
+        &StateV2 {
+            counter: RefCell::new(2),
+            limit: RefCell::new(3),
+        },
+    );
+
+    let states: Vec<StateV2> = golden_files
+        .assert_decodable()
+        .into_iter()
+        .map(|(_, state)| state)
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            StateV2 {
+                counter: RefCell::new(1),
+                limit: RefCell::new(10),
+            },
+            StateV2 {
+                counter: RefCell::new(2),
+                limit: RefCell::new(3),
+            },
+        ]
+    );
+}
+
+#[rstest]
+#[should_panic(expected = "basic.v1.state")]
+fn test_incompatible_state_fails() {
+    let golden_files = golden_files("incompatible_state");
+    golden_files.record("basic", &state_v1(1));
+    golden_files.assert_decodable::<IncompatibleState>();
+}
+
+#[rstest]
+#[should_panic(expected = "must only contain")]
+fn test_invalid_name() {
+    golden_files("invalid_name").record("../basic", &state_v1(1));
+}
@@ -1,3 +1,4 @@
 pub mod canister_api;
+pub mod golden;
 pub mod ic_api;
 pub mod principal;
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use candid::parser::types::{IDLProg, IDLTypes};
+use candid::parser::typing::{ast_to_type, check_prog, TypeEnv};
+use candid::{decode_one, IDLArgs};
+use clap::Args;
+use serde_json::{json, Value};
+
+use common::constants::STATE_MAX_DECOMPRESSED_SIZE;
+use common::dto::{decompress, StateExportData, StateExportResponse};
+use common::state::transfer::sha256_hex;
+use common::state::StateEnvelope;
+
+use crate::json::idl_args_to_json;
+
+#[derive(Args)]
+pub struct DecodeOptions {
+    /// Candid file with the type definitions used by --types
+    #[arg(long)]
+    did: Option<PathBuf>,
+    /// Candid types of the state payload tuple, e.g. "(nat64, vec record { text; principal })".
+    /// Without it, field names are shown as hashes.
+    #[arg(long)]
+    types: Option<String>,
+    /// Hex encoded X25519 secret key of the state exporter, for encrypted exports
+    #[arg(long, env = "STATE_EXPORTER_SECRET_KEY", hide_env_values = true)]
+    secret_key: Option<String>,
+}
+
+pub struct DecodedState {
+    pub schema_version: u32,
+    pub exported_at: u64,
+    pub encrypted: bool,
+    pub args: IDLArgs,
+}
+
+impl DecodedState {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "schema_version": self.schema_version,
+            "exported_at": self.exported_at,
+            "encrypted": self.encrypted,
+            "state": idl_args_to_json(&self.args),
+        })
+    }
+}
+
+/// Decodes a `StateExportData`, or the `StateExportResponse` of `export_state` holding one.
+fn decode_export_data(data: &[u8]) -> Result<StateExportData> {
+    if let Ok(export_data) = decode_one::<StateExportData>(data) {
+        return Ok(export_data);
+    }
+    decode_one::<StateExportResponse>(data)
+        .context("expected a candid encoded StateExportData or StateExportResponse")?
+        .into_result()
+        .map_err(|err| anyhow!("export failed: {} ({})", err.message, err.code))
+}
+
+fn check_sha256(data: &[u8], expected: &str) -> Result<()> {
+    let sha256 = sha256_hex(data);
+    if sha256 != expected {
+        return Err(anyhow!(
+            "checksum mismatch, expected {} but state has {}",
+            expected,
+            sha256
+        ));
+    }
+    Ok(())
+}
+
+/// The encrypted data is checked against `encrypted_sha256` before it is decrypted, the
+/// decrypted state is checked against `sha256` like an unencrypted one.
+fn decrypt_if_needed(export_data: &StateExportData, options: &DecodeOptions) -> Result<Vec<u8>> {
+    if !export_data.encrypted {
+        return Ok(export_data.state_data.clone());
+    }
+    let encrypted_sha256 = export_data
+        .encrypted_sha256
+        .as_ref()
+        .ok_or_else(|| anyhow!("state is encrypted but has no encrypted_sha256"))?;
+    check_sha256(&export_data.state_data, encrypted_sha256)?;
+    let secret_key = options
+        .secret_key
+        .as_ref()
+        .ok_or_else(|| anyhow!("state is encrypted, --secret-key is required"))?;
+    let secret_key = state_crypto::parse_key_hex(secret_key)?;
+    Ok(state_crypto::decrypt(&export_data.state_data, &secret_key)?)
+}
+
+fn decode_payload(payload: &[u8], options: &DecodeOptions) -> Result<IDLArgs> {
+    let types = match &options.types {
+        Some(types) => types,
+        None => return Ok(IDLArgs::from_bytes(payload)?),
+    };
+    let mut env = TypeEnv::new();
+    if let Some(did) = &options.did {
+        let did =
+            fs::read_to_string(did).with_context(|| format!("failed to read {}", did.display()))?;
+        let prog: IDLProg = did.parse()?;
+        check_prog(&mut env, &prog)?;
+    }
+    let types: IDLTypes = types.parse()?;
+    let types = types
+        .args
+        .iter()
+        .map(|ty| ast_to_type(&env, ty))
+        .collect::<candid::Result<Vec<_>>>()?;
+    Ok(IDLArgs::from_bytes_with_types(payload, &env, &types)?)
+}
+
+/// Reads an exported state: decrypts, decompresses, verifies the checksum and the envelope and
+/// decodes the payload.
+pub fn load_state(path: &Path, options: &DecodeOptions) -> Result<DecodedState> {
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let export_data = decode_export_data(&data)?;
+    let data = decrypt_if_needed(&export_data, options)?;
+    let state_data = decompress(&data, STATE_MAX_DECOMPRESSED_SIZE)?;
+    check_sha256(&state_data, &export_data.sha256)?;
+    let envelope = StateEnvelope::from_bytes(&state_data)?;
+    if envelope.schema_version != export_data.schema_version {
+        return Err(anyhow!(
+            "schema version mismatch, export has {} but envelope has {}",
+            export_data.schema_version,
+            envelope.schema_version
+        ));
+    }
+    let args = decode_payload(&envelope.payload, options)?;
+    Ok(DecodedState {
+        schema_version: envelope.schema_version,
+        exported_at: export_data.exported_at,
+        encrypted: export_data.encrypted,
+        args,
+    })
+}
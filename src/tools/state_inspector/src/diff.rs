@@ -0,0 +1,143 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use serde_json::Value;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {}", path, value),
+            Change::Removed { path, value } => write!(f, "- {}: {}", path, value),
+            Change::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// Candid maps are encoded as `vec record { key; value }`, which decode to arrays of
+/// `{"0": key, "1": value}`. Returns the entries keyed by `key` so maps are compared
+/// regardless of their order.
+fn as_map_entries(values: &[Value]) -> Option<BTreeMap<String, &Value>> {
+    let mut entries = BTreeMap::new();
+    for value in values {
+        let object = value.as_object()?;
+        if object.len() != 2 || !object.contains_key("1") {
+            return None;
+        }
+        let key = match object.get("0")? {
+            Value::String(key) => key.clone(),
+            key => key.to_string(),
+        };
+        if entries.insert(key, &object["1"]).is_some() {
+            return None;
+        }
+    }
+    Some(entries)
+}
+
+fn diff_maps(
+    path: &str,
+    old: BTreeMap<String, &Value>,
+    new: BTreeMap<String, &Value>,
+    changes: &mut Vec<Change>,
+) {
+    for (key, old_value) in old.iter() {
+        let child = format!("{}[{:?}]", path, key);
+        match new.get(key) {
+            Some(new_value) => diff_at(&child, old_value, new_value, changes),
+            None => changes.push(Change::Removed {
+                path: child,
+                value: (*old_value).clone(),
+            }),
+        }
+    }
+    for (key, new_value) in new.iter() {
+        if !old.contains_key(key) {
+            changes.push(Change::Added {
+                path: format!("{}[{:?}]", path, key),
+                value: (*new_value).clone(),
+            });
+        }
+    }
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old.iter() {
+                let child = format!("{}.{}", path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_at(&child, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        path: child,
+                        value: old_value.clone(),
+                    }),
+                }
+            }
+            for (key, new_value) in new.iter() {
+                if !old.contains_key(key) {
+                    changes.push(Change::Added {
+                        path: format!("{}.{}", path, key),
+                        value: new_value.clone(),
+                    });
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            if let (Some(old_entries), Some(new_entries)) =
+                (as_map_entries(old), as_map_entries(new))
+            {
+                diff_maps(path, old_entries, new_entries, changes);
+                return;
+            }
+            for (index, old_value) in old.iter().enumerate() {
+                let child = format!("{}[{}]", path, index);
+                match new.get(index) {
+                    Some(new_value) => diff_at(&child, old_value, new_value, changes),
+                    None => changes.push(Change::Removed {
+                        path: child,
+                        value: old_value.clone(),
+                    }),
+                }
+            }
+            for (index, new_value) in new.iter().enumerate().skip(old.len()) {
+                changes.push(Change::Added {
+                    path: format!("{}[{}]", path, index),
+                    value: new_value.clone(),
+                });
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Changed {
+            path: path.to_string(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// Compares two decoded states field by field.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    diff_at("$", old, new, &mut changes);
+    changes
+}
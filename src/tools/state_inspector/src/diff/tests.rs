@@ -0,0 +1,78 @@
+use rstest::*;
+use serde_json::json;
+
+use super::*;
+
+#[rstest]
+fn test_no_differences() {
+    let state = json!([12, {"limit": 1, "name": "test"}]);
+    assert_eq!(diff(&state, &state), vec![]);
+}
+
+#[rstest]
+fn test_changed_added_and_removed_fields() {
+    let old = json!([12, {"limit": 1, "name": "test"}]);
+    let new = json!([13, {"limit": 1, "end_at": 100}, "new"]);
+    assert_eq!(
+        diff(&old, &new),
+        vec![
+            Change::Changed {
+                path: "$[0]".to_string(),
+                old: json!(12),
+                new: json!(13),
+            },
+            Change::Removed {
+                path: "$[1].name".to_string(),
+                value: json!("test"),
+            },
+            Change::Added {
+                path: "$[1].end_at".to_string(),
+                value: json!(100),
+            },
+            Change::Added {
+                path: "$[2]".to_string(),
+                value: json!("new"),
+            },
+        ]
+    );
+}
+
+#[rstest]
+fn test_maps_are_compared_by_key() {
+    let old = json!([[
+        {"0": "a.ic", "1": "aaaaa-aa"},
+        {"0": "b.ic", "1": "2vxsx-fae"},
+    ]]);
+    let new = json!([[
+        {"0": "c.ic", "1": "aaaaa-aa"},
+        {"0": "b.ic", "1": "aaaaa-aa"},
+    ]]);
+    assert_eq!(
+        diff(&old, &new),
+        vec![
+            Change::Removed {
+                path: "$[0][\"a.ic\"]".to_string(),
+                value: json!("aaaaa-aa"),
+            },
+            Change::Changed {
+                path: "$[0][\"b.ic\"]".to_string(),
+                old: json!("2vxsx-fae"),
+                new: json!("aaaaa-aa"),
+            },
+            Change::Added {
+                path: "$[0][\"c.ic\"]".to_string(),
+                value: json!("aaaaa-aa"),
+            },
+        ]
+    );
+}
+
+#[rstest]
+fn test_change_display() {
+    let change = Change::Changed {
+        path: "$[0]".to_string(),
+        old: json!(1),
+        new: json!(2),
+    };
+    assert_eq!(change.to_string(), "~ $[0]: 1 -> 2");
+}
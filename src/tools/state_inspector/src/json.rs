@@ -0,0 +1,47 @@
+use candid::parser::value::{IDLField, IDLValue};
+use candid::IDLArgs;
+use serde_json::{Map, Value};
+
+#[cfg(test)]
+mod tests;
+
+pub fn idl_args_to_json(args: &IDLArgs) -> Value {
+    Value::Array(args.args.iter().map(idl_value_to_json).collect())
+}
+
+fn fields_to_json(fields: &[IDLField]) -> Value {
+    let mut map = Map::new();
+    for field in fields {
+        map.insert(field.id.to_string(), idl_value_to_json(&field.val));
+    }
+    Value::Object(map)
+}
+
+/// Converts a candid value to JSON, `nat` and `int` are written as strings since they are unbounded.
+pub fn idl_value_to_json(value: &IDLValue) -> Value {
+    match value {
+        IDLValue::Null | IDLValue::None | IDLValue::Reserved => Value::Null,
+        IDLValue::Bool(b) => Value::Bool(*b),
+        IDLValue::Text(text) => Value::String(text.clone()),
+        IDLValue::Principal(principal) | IDLValue::Service(principal) => {
+            Value::String(principal.to_text())
+        }
+        IDLValue::Nat(nat) => Value::String(nat.0.to_string()),
+        IDLValue::Int(int) => Value::String(int.0.to_string()),
+        IDLValue::Nat8(n) => Value::from(*n),
+        IDLValue::Nat16(n) => Value::from(*n),
+        IDLValue::Nat32(n) => Value::from(*n),
+        IDLValue::Nat64(n) => Value::from(*n),
+        IDLValue::Int8(n) => Value::from(*n),
+        IDLValue::Int16(n) => Value::from(*n),
+        IDLValue::Int32(n) => Value::from(*n),
+        IDLValue::Int64(n) => Value::from(*n),
+        IDLValue::Float32(n) => Value::from(*n),
+        IDLValue::Float64(n) => Value::from(*n),
+        IDLValue::Opt(value) => idl_value_to_json(value),
+        IDLValue::Vec(values) => Value::Array(values.iter().map(idl_value_to_json).collect()),
+        IDLValue::Record(fields) => fields_to_json(fields),
+        IDLValue::Variant(variant) => fields_to_json(std::slice::from_ref(&*variant.0)),
+        value => Value::String(value.to_string()),
+    }
+}
@@ -0,0 +1,42 @@
+use candid::parser::value::VariantValue;
+use candid::types::Label;
+use candid::{Nat, Principal};
+use rstest::*;
+use serde_json::json;
+
+use super::*;
+
+fn field(name: &str, val: IDLValue) -> IDLField {
+    IDLField {
+        id: Label::Named(name.to_string()),
+        val,
+    }
+}
+
+#[rstest]
+#[case(IDLValue::Bool(true), json!(true))]
+#[case(IDLValue::Text("test".to_string()), json!("test"))]
+#[case(IDLValue::Nat(Nat::from(12u64)), json!("12"))]
+#[case(IDLValue::Nat64(u64::MAX), json!(u64::MAX))]
+#[case(IDLValue::Int8(-1), json!(-1))]
+#[case(IDLValue::None, json!(null))]
+#[case(IDLValue::Opt(Box::new(IDLValue::Nat32(1))), json!(1))]
+#[case(IDLValue::Principal(Principal::anonymous()), json!("2vxsx-fae"))]
+fn test_primitive_to_json(#[case] value: IDLValue, #[case] expected: Value) {
+    assert_eq!(idl_value_to_json(&value), expected);
+}
+
+#[rstest]
+fn test_record_and_variant_to_json() {
+    let value = IDLValue::Vec(vec![IDLValue::Record(vec![
+        field("name", IDLValue::Text("test".to_string())),
+        field(
+            "status",
+            IDLValue::Variant(VariantValue(Box::new(field("Active", IDLValue::Null)), 0)),
+        ),
+    ])]);
+    assert_eq!(
+        idl_value_to_json(&value),
+        json!([{"name": "test", "status": {"Active": null}}])
+    );
+}
@@ -0,0 +1,77 @@
+//! Offline inspector for state exported by `export_state`.
+//!
+//! ```text
+//! state_inspector inspect state.bin --did state.did --types "(Registries, Approvals)"
+//! state_inspector diff before.bin after.bin --types "(vec record { text; principal })"
+//! ```
+//!
+//! The input file holds a candid encoded `StateExportData`, or the `StateExportResponse` returned by
+//! `export_state`. Encrypted exports are decrypted with `--secret-key` or the
+//! `STATE_EXPORTER_SECRET_KEY` environment variable.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::decode::{load_state, DecodeOptions};
+use crate::diff::diff;
+
+mod decode;
+mod diff;
+mod json;
+
+#[derive(Parser)]
+#[command(
+    name = "state_inspector",
+    about = "Inspect state exported by export_state"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print an exported state as JSON
+    Inspect {
+        file: PathBuf,
+        #[command(flatten)]
+        options: DecodeOptions,
+    },
+    /// Print the fields which differ between two exported states
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        #[command(flatten)]
+        options: DecodeOptions,
+    },
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Inspect { file, options } => {
+            let state = load_state(&file, &options)?;
+            println!("{}", serde_json::to_string_pretty(&state.to_json())?);
+        }
+        Command::Diff { old, new, options } => {
+            let old = load_state(&old, &options)?;
+            let new = load_state(&new, &options)?;
+            if old.schema_version != new.schema_version {
+                println!(
+                    "~ schema_version: {} -> {}",
+                    old.schema_version, new.schema_version
+                );
+            }
+            let changes = diff(&old.to_json()["state"], &new.to_json()["state"]);
+            if changes.is_empty() {
+                println!("no differences");
+            }
+            for change in changes {
+                println!("{}", change);
+            }
+        }
+    }
+    Ok(())
+}